@@ -0,0 +1,1053 @@
+//! Off-chain helpers building escrow instructions and decoding escrow accounts,
+//! enabled with the `client` feature.
+
+use solana_sdk::{
+    bpf_loader_upgradeable,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+};
+
+pub use crate::{
+    error::EscrowError,
+    instructions::{Resolution, EXPIRE_TIP},
+    state::{
+        Approvals, Auction, Basket, BasketLeg, Config, Dutch, Escrow, Htlc, Vesting, MAX_APPROVERS, MAX_BASKET_LEGS, HTLC, MULTISIG, NFT_X, NFT_Y, STATUS_DISPUTED,
+        STATUS_OPEN, VESTING, AUCTION, DUTCH,
+    },
+};
+use crate::instructions::{EscrowInstructions, InitApprovalsArgs, InitConfigArgs, MakeArgs, MakeBasketArgs, ResolveArgs, UpdateArgs};
+
+/// Program id of the escrow program.
+pub const ID: Pubkey = Pubkey::new_from_array(crate::ID);
+
+/// Derives the escrow account of `maker` for `seed`.
+pub fn escrow_address(maker: &Pubkey, seed: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"escrow", maker.as_ref(), &seed.to_le_bytes()], &ID)
+}
+
+/// Derives the vault holding the `mint_x` tokens offered by `escrow`.
+pub fn vault_address(escrow: &Pubkey, mint_x: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"vault", escrow.as_ref(), mint_x.as_ref()], &ID)
+}
+
+/// Derives the bid vault holding the `mint_y` bids on the auction `escrow`.
+pub fn bid_vault_address(escrow: &Pubkey, mint_y: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"bid_vault", escrow.as_ref(), mint_y.as_ref()], &ID)
+}
+
+/// Program id of the Metaplex token metadata program.
+pub const METADATA_PROGRAM_ID: Pubkey = Pubkey::new_from_array(crate::metadata::METADATA_PROGRAM_ID);
+
+/// Derives the Metaplex metadata account of `mint`.
+pub fn metadata_address(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"metadata", METADATA_PROGRAM_ID.as_ref(), mint.as_ref()], &METADATA_PROGRAM_ID).0
+}
+
+/// Derives the basket account of `maker` for `seed`.
+pub fn basket_address(maker: &Pubkey, seed: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"basket", maker.as_ref(), &seed.to_le_bytes()], &ID)
+}
+
+/// Derives the global config account.
+pub fn config_address() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"config"], &ID)
+}
+
+/// Decodes the data of the config account, `None` if it has the wrong size.
+pub fn decode_config(data: &[u8]) -> Option<Config> {
+    if data.len() != Config::LEN {
+        return None;
+    }
+    Some(unsafe { core::ptr::read_unaligned(data.as_ptr() as *const Config) })
+}
+
+/// Derives the approvals account of a multisig `escrow`.
+pub fn approvals_address(escrow: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"approvals", escrow.as_ref()], &ID)
+}
+
+/// Decodes the data of an approvals account, `None` if it has the wrong size.
+pub fn decode_approvals(data: &[u8]) -> Option<Approvals> {
+    decode_state(data, Approvals::LEN)
+}
+
+/// Decodes the data of a basket account, `None` if it has the wrong size.
+pub fn decode_basket(data: &[u8]) -> Option<Basket> {
+    decode_state(data, Basket::LEN)
+}
+
+/// Decodes the data of an escrow account, `None` if it has the wrong size.
+pub fn decode_escrow(data: &[u8]) -> Option<Escrow> {
+    let escrow: Escrow = decode_state(data.get(..Escrow::LEN)?, Escrow::LEN)?;
+    (data.len() == Escrow::space(escrow.flags)).then_some(escrow)
+}
+
+/// Decodes the section of an `HTLC` escrow account, `None` for another escrow.
+pub fn decode_htlc(data: &[u8]) -> Option<Htlc> {
+    decode_escrow(data).filter(Escrow::is_htlc)?;
+    decode_state(&data[Escrow::LEN..], Htlc::LEN)
+}
+
+/// Decodes the section of a `VESTING` escrow account, `None` for another escrow.
+pub fn decode_vesting(data: &[u8]) -> Option<Vesting> {
+    decode_escrow(data).filter(Escrow::is_vesting)?;
+    decode_state(&data[Escrow::LEN..], Vesting::LEN)
+}
+
+/// Decodes the section of an `AUCTION` escrow account, `None` for another escrow.
+pub fn decode_auction(data: &[u8]) -> Option<Auction> {
+    decode_escrow(data).filter(Escrow::is_auction)?;
+    decode_state(&data[Escrow::LEN..], Auction::LEN)
+}
+
+/// Decodes the section of a `DUTCH` escrow account, `None` for another escrow.
+pub fn decode_dutch(data: &[u8]) -> Option<Dutch> {
+    decode_escrow(data).filter(Escrow::is_dutch)?;
+    decode_state(&data[Escrow::LEN..], Dutch::LEN)
+}
+
+/// Copies the `len` bytes of account data into a zeroed `T`, `None` if the data has
+/// another size or `len` isn't the size of the struct.
+fn decode_state<T: Default>(data: &[u8], len: usize) -> Option<T> {
+    if data.len() != len || len != core::mem::size_of::<T>() {
+        return None;
+    }
+    let mut state = T::default();
+    unsafe { core::ptr::copy_nonoverlapping(data.as_ptr(), &mut state as *mut T as *mut u8, len) };
+    Some(state)
+}
+
+/// Parameters of [`make_ix`]. Use the system program as a mint to trade lamports
+/// on that side, in which case `maker_ata_x` and `token_program` are not used.
+pub struct MakeParams {
+    pub maker: Pubkey,
+    pub mint_x: Pubkey,
+    pub mint_y: Pubkey,
+    pub maker_ata_x: Pubkey,
+    /// Token program owning `mint_x`.
+    pub token_program: Pubkey,
+    pub seed: u64,
+    /// Amount of mint_y requested, the reserve price of an auction or the start price of a Dutch auction.
+    pub amount: u64,
+    /// Amount of mint_x deposited.
+    pub deposit: u64,
+    /// Unix timestamp after which the offer can't be taken, `0` for none. The end of an auction.
+    pub expiry: i64,
+    /// Only taker allowed to fill the offer, `None` for anyone.
+    pub taker: Option<Pubkey>,
+    /// `NFT_X` and/or `NFT_Y` to trade either side as an NFT.
+    pub flags: u8,
+    /// Collection mint_y has to be a verified member of, `None` for any.
+    pub collection: Option<Pubkey>,
+    /// Third party settling disputes, `None` for none. Requires a `taker`.
+    pub arbiter: Option<Pubkey>,
+    /// SHA-256 hashlock making this a hash time-locked escrow, `None` for a regular one.
+    /// `expiry` is then its timelock and `taker` the recipient.
+    pub hashlock: Option<[u8; 32]>,
+    /// Schedule making this a vesting grant of the deposit to `taker`, `None` for a regular escrow.
+    pub vesting: Option<VestingSchedule>,
+    /// Terms making this an English auction of the deposit, `None` for a regular escrow.
+    pub auction: Option<AuctionParams>,
+    /// Price decay making this a Dutch auction, `None` for a fixed price.
+    pub dutch: Option<DutchAuction>,
+}
+
+/// Price decay of a Dutch auction from `amount` down to `floor`, between unix timestamps.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DutchAuction {
+    pub floor: u64,
+    pub start: i64,
+    pub end: i64,
+    /// Seconds between price drops, `0` for a linear decay.
+    pub step: i64,
+}
+
+/// Terms of an auction opened by [`make_ix`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AuctionParams {
+    /// Smallest raise over the highest bid.
+    pub min_increment: u64,
+    /// Token program owning `mint_y`, creating the bid vault. Not used for lamport bids.
+    pub token_program_y: Pubkey,
+}
+
+/// Unlock schedule of a vesting escrow, as unix timestamps.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct VestingSchedule {
+    pub start: i64,
+    /// Nothing is unlocked before the cliff, `0` for none.
+    pub cliff: i64,
+    pub end: i64,
+}
+
+/// Builds a `Make` instruction, deriving the escrow and its vault.
+pub fn make_ix(params: &MakeParams) -> Instruction {
+    let (escrow, bump) = escrow_address(&params.maker, params.seed);
+    let (vault, vault_bump) = vault_or_system_program(&escrow, &params.mint_x);
+
+    let mut flags = params.flags;
+    if params.hashlock.is_some() {
+        flags |= HTLC;
+    }
+    if params.vesting.is_some() {
+        flags |= VESTING;
+    }
+    if params.auction.is_some() {
+        flags |= AUCTION;
+    }
+    if params.dutch.is_some() {
+        flags |= DUTCH;
+    }
+
+    let mut data = Vec::with_capacity(1 + MakeArgs::len(flags));
+    data.push(EscrowInstructions::Make as u8);
+    data.push(bump);
+    data.extend_from_slice(&params.seed.to_le_bytes());
+    data.extend_from_slice(&params.amount.to_le_bytes());
+    data.extend_from_slice(&params.deposit.to_le_bytes());
+    data.extend_from_slice(&params.expiry.to_le_bytes());
+    data.extend_from_slice(params.taker.unwrap_or_default().as_ref());
+    data.push(vault_bump);
+    data.push(flags);
+    data.extend_from_slice(params.collection.unwrap_or_default().as_ref());
+    data.extend_from_slice(params.arbiter.unwrap_or_default().as_ref());
+    // The arguments of the mode follow
+    if let Some(hashlock) = params.hashlock {
+        data.extend_from_slice(&hashlock);
+    }
+    if let Some(vesting) = params.vesting {
+        data.extend_from_slice(&vesting.start.to_le_bytes());
+        data.extend_from_slice(&vesting.cliff.to_le_bytes());
+        data.extend_from_slice(&vesting.end.to_le_bytes());
+    }
+    let (bid_vault, bid_vault_bump) = bid_vault_or_system_program(&escrow, &params.mint_y);
+    if let Some(auction) = params.auction {
+        data.extend_from_slice(&auction.min_increment.to_le_bytes());
+        data.push(bid_vault_bump);
+    }
+    if let Some(dutch) = params.dutch {
+        data.extend_from_slice(&dutch.floor.to_le_bytes());
+        data.extend_from_slice(&dutch.start.to_le_bytes());
+        data.extend_from_slice(&dutch.end.to_le_bytes());
+        data.extend_from_slice(&dutch.step.to_le_bytes());
+    }
+
+    let mut accounts = vec![
+        AccountMeta::new(params.maker, true),
+        AccountMeta::new_readonly(params.mint_x, false),
+        AccountMeta::new_readonly(params.mint_y, false),
+        AccountMeta::new(params.maker_ata_x, false),
+        AccountMeta::new(vault, false),
+        AccountMeta::new(escrow, false),
+        AccountMeta::new_readonly(system_program::ID, false),
+        AccountMeta::new_readonly(params.token_program, false),
+        AccountMeta::new_readonly(config_address().0, false),
+    ];
+    if params.collection.is_some() {
+        accounts.push(AccountMeta::new_readonly(metadata_address(&params.mint_y), false));
+    }
+    if let Some(auction) = params.auction {
+        accounts.push(AccountMeta::new(bid_vault, false));
+        accounts.push(AccountMeta::new_readonly(auction.token_program_y, false));
+    }
+
+    Instruction::new_with_bytes(ID, &data, accounts)
+}
+
+/// Parameters of [`take_ix`]. The token accounts and token program of a lamport
+/// side are not used and can be set to the system program.
+pub struct TakeParams {
+    pub taker: Pubkey,
+    pub maker: Pubkey,
+    pub seed: u64,
+    pub mint_x: Pubkey,
+    pub mint_y: Pubkey,
+    pub taker_ata_x: Pubkey,
+    pub taker_ata_y: Pubkey,
+    pub maker_ata_y: Pubkey,
+    /// Token program owning `mint_x`.
+    pub token_program_x: Pubkey,
+    /// Token program owning `mint_y`.
+    pub token_program_y: Pubkey,
+    /// Amount of mint_y paid.
+    pub fill: u64,
+    /// `version` of the escrow the terms were read from, the take fails if the
+    /// maker updated them since.
+    pub version: u64,
+    /// Token account of the config treasury for mint_y receiving the protocol fee,
+    /// the treasury itself when paying lamports. Not used while the fee is zero.
+    pub treasury: Pubkey,
+    /// Account receiving the referrer fee, a mint_y token account or a wallet when
+    /// paying lamports. `None` when there is no referrer.
+    pub referrer: Option<Pubkey>,
+    /// Share of the payment granted to the referrer, in basis points.
+    pub referrer_bps: u16,
+    /// Whether the escrow is a `MULTISIG` escrow, passing its approvals.
+    pub multisig: bool,
+}
+
+/// Builds a `Take` instruction, deriving the escrow and its vault.
+pub fn take_ix(params: &TakeParams) -> Instruction {
+    let (escrow, _) = escrow_address(&params.maker, params.seed);
+    let (vault, _) = vault_or_system_program(&escrow, &params.mint_x);
+
+    let mut data = vec![EscrowInstructions::Take as u8];
+    data.extend_from_slice(&params.fill.to_le_bytes());
+    data.extend_from_slice(&params.version.to_le_bytes());
+    data.extend_from_slice(&params.referrer_bps.to_le_bytes());
+
+    let mut accounts = vec![
+        AccountMeta::new(params.taker, true),
+        AccountMeta::new(params.maker, false),
+        AccountMeta::new(params.mint_x, false),
+        AccountMeta::new_readonly(params.mint_y, false),
+        AccountMeta::new(params.taker_ata_x, false),
+        AccountMeta::new(params.taker_ata_y, false),
+        AccountMeta::new(params.maker_ata_y, false),
+        AccountMeta::new(vault, false),
+        AccountMeta::new(escrow, false),
+        AccountMeta::new_readonly(params.token_program_x, false),
+        AccountMeta::new_readonly(params.token_program_y, false),
+        AccountMeta::new_readonly(system_program::ID, false),
+        AccountMeta::new_readonly(config_address().0, false),
+        AccountMeta::new(params.treasury, false),
+    ];
+    if params.multisig {
+        accounts.push(AccountMeta::new(approvals_address(&escrow).0, false));
+    }
+    if let Some(referrer) = params.referrer {
+        accounts.push(AccountMeta::new(referrer, false));
+    }
+
+    Instruction::new_with_bytes(ID, &data, accounts)
+}
+
+/// Parameters of [`refund_ix`].
+pub struct RefundParams {
+    pub maker: Pubkey,
+    pub seed: u64,
+    pub mint_x: Pubkey,
+    pub maker_ata_x: Pubkey,
+    /// Token program owning `mint_x`.
+    pub token_program: Pubkey,
+    /// Whether the escrow is a `MULTISIG` escrow, closing its approvals.
+    pub multisig: bool,
+    /// `mint_y` and the token program owning it for an `AUCTION` escrow, closing its bid vault.
+    pub auction: Option<(Pubkey, Pubkey)>,
+}
+
+/// Builds a `Refund` instruction, deriving the escrow and its vault.
+pub fn refund_ix(params: &RefundParams) -> Instruction {
+    let (escrow, _) = escrow_address(&params.maker, params.seed);
+    let (vault, _) = vault_or_system_program(&escrow, &params.mint_x);
+
+    let mut accounts = vec![
+        AccountMeta::new(params.maker, true),
+        AccountMeta::new(params.mint_x, false),
+        AccountMeta::new(params.maker_ata_x, false),
+        AccountMeta::new(vault, false),
+        AccountMeta::new(escrow, false),
+        AccountMeta::new_readonly(params.token_program, false),
+        AccountMeta::new_readonly(system_program::ID, false),
+    ];
+    if params.multisig {
+        accounts.push(AccountMeta::new(approvals_address(&escrow).0, false));
+    }
+    if let Some((mint_y, token_program_y)) = params.auction {
+        accounts.push(AccountMeta::new(bid_vault_or_system_program(&escrow, &mint_y).0, false));
+        accounts.push(AccountMeta::new_readonly(token_program_y, false));
+    }
+
+    Instruction::new_with_bytes(ID, &[EscrowInstructions::Refund as u8], accounts)
+}
+
+/// Builds an `Expire` instruction refunding an expired escrow to its maker on behalf
+/// of `keeper`, who receives `EXPIRE_TIP`. Takes the same parameters as [`refund_ix`].
+pub fn expire_ix(keeper: &Pubkey, params: &RefundParams) -> Instruction {
+    let mut instruction = refund_ix(params);
+    instruction.data = vec![EscrowInstructions::Expire as u8];
+
+    // The maker doesn't sign, the keeper comes first and signs instead
+    instruction.accounts[0].is_signer = false;
+    instruction.accounts.insert(0, AccountMeta::new(*keeper, true));
+    instruction
+}
+
+/// Parameters of [`claim_ix`]. The token account and token program are not used
+/// when the escrow offers lamports and can be set to the system program.
+pub struct ClaimParams {
+    pub maker: Pubkey,
+    pub taker: Pubkey,
+    pub seed: u64,
+    pub mint_x: Pubkey,
+    pub taker_ata_x: Pubkey,
+    /// Token program owning `mint_x`.
+    pub token_program: Pubkey,
+    /// Secret whose SHA-256 is the escrow hashlock.
+    pub preimage: [u8; 32],
+}
+
+/// Builds a `Claim` instruction releasing a hash time-locked escrow to its taker,
+/// deriving the escrow and its vault.
+pub fn claim_ix(params: &ClaimParams) -> Instruction {
+    let (escrow, _) = escrow_address(&params.maker, params.seed);
+    let (vault, _) = vault_or_system_program(&escrow, &params.mint_x);
+
+    let mut data = vec![EscrowInstructions::Claim as u8];
+    data.extend_from_slice(&params.preimage);
+
+    Instruction::new_with_bytes(
+        ID,
+        &data,
+        vec![
+            AccountMeta::new(params.maker, false),
+            AccountMeta::new(params.taker, false),
+            AccountMeta::new(params.mint_x, false),
+            AccountMeta::new(params.taker_ata_x, false),
+            AccountMeta::new(vault, false),
+            AccountMeta::new(escrow, false),
+            AccountMeta::new_readonly(params.token_program, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+/// Parameters of [`claim_vested_ix`]. The token account and token program are not used
+/// when the escrow offers lamports and can be set to the system program.
+pub struct ClaimVestedParams {
+    pub maker: Pubkey,
+    pub taker: Pubkey,
+    pub seed: u64,
+    pub mint_x: Pubkey,
+    pub taker_ata_x: Pubkey,
+    /// Token program owning `mint_x`.
+    pub token_program: Pubkey,
+}
+
+/// Builds a `ClaimVested` instruction paying what vested so far to the taker of a
+/// vesting escrow, deriving the escrow and its vault.
+pub fn claim_vested_ix(params: &ClaimVestedParams) -> Instruction {
+    let (escrow, _) = escrow_address(&params.maker, params.seed);
+    let (vault, _) = vault_or_system_program(&escrow, &params.mint_x);
+
+    Instruction::new_with_bytes(
+        ID,
+        &[EscrowInstructions::ClaimVested as u8],
+        vec![
+            AccountMeta::new(params.maker, false),
+            AccountMeta::new(params.taker, false),
+            AccountMeta::new(params.mint_x, false),
+            AccountMeta::new(params.taker_ata_x, false),
+            AccountMeta::new(vault, false),
+            AccountMeta::new(escrow, false),
+            AccountMeta::new_readonly(params.token_program, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+/// Parameters of [`bid_ix`]. The token accounts and token program are not used for
+/// lamport bids and can be set to the system program.
+pub struct BidParams {
+    pub bidder: Pubkey,
+    pub maker: Pubkey,
+    pub seed: u64,
+    pub mint_y: Pubkey,
+    pub bidder_ata_y: Pubkey,
+    /// Current highest bidder, refunded by the bid. The system program for the first bid.
+    pub previous_bidder: Pubkey,
+    /// mint_y token account of the current highest bidder. The system program for the first bid.
+    pub previous_bidder_ata_y: Pubkey,
+    /// Token program owning `mint_y`.
+    pub token_program_y: Pubkey,
+    /// Amount of mint_y bid.
+    pub amount: u64,
+}
+
+/// Builds a `Bid` instruction on an auction, deriving the escrow and its bid vault.
+pub fn bid_ix(params: &BidParams) -> Instruction {
+    let (escrow, _) = escrow_address(&params.maker, params.seed);
+    let (bid_vault, _) = bid_vault_or_system_program(&escrow, &params.mint_y);
+
+    let mut data = vec![EscrowInstructions::Bid as u8];
+    data.extend_from_slice(&params.amount.to_le_bytes());
+
+    Instruction::new_with_bytes(
+        ID,
+        &data,
+        vec![
+            AccountMeta::new(params.bidder, true),
+            AccountMeta::new_readonly(params.maker, false),
+            AccountMeta::new_readonly(params.mint_y, false),
+            AccountMeta::new(params.bidder_ata_y, false),
+            AccountMeta::new(bid_vault, false),
+            AccountMeta::new(escrow, false),
+            AccountMeta::new(params.previous_bidder, false),
+            AccountMeta::new(params.previous_bidder_ata_y, false),
+            AccountMeta::new_readonly(params.token_program_y, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+            AccountMeta::new_readonly(config_address().0, false),
+        ],
+    )
+}
+
+/// Parameters of [`settle_ix`]. The token accounts and token program of a lamport
+/// side are not used and can be set to the system program.
+pub struct SettleParams {
+    pub maker: Pubkey,
+    /// Highest bidder of the auction.
+    pub winner: Pubkey,
+    pub seed: u64,
+    pub mint_x: Pubkey,
+    pub mint_y: Pubkey,
+    pub winner_ata_x: Pubkey,
+    pub maker_ata_y: Pubkey,
+    /// Token program owning `mint_x`.
+    pub token_program_x: Pubkey,
+    /// Token program owning `mint_y`.
+    pub token_program_y: Pubkey,
+    /// Token account of the config treasury for mint_y receiving the protocol fee,
+    /// the treasury itself for lamport bids. Not used while the fee is zero.
+    pub treasury: Pubkey,
+}
+
+/// Builds a `Settle` instruction closing an ended auction, deriving the escrow and
+/// its vaults.
+pub fn settle_ix(params: &SettleParams) -> Instruction {
+    let (escrow, _) = escrow_address(&params.maker, params.seed);
+    let (vault, _) = vault_or_system_program(&escrow, &params.mint_x);
+    let (bid_vault, _) = bid_vault_or_system_program(&escrow, &params.mint_y);
+
+    Instruction::new_with_bytes(
+        ID,
+        &[EscrowInstructions::Settle as u8],
+        vec![
+            AccountMeta::new(params.maker, false),
+            AccountMeta::new(params.winner, false),
+            AccountMeta::new(params.mint_x, false),
+            AccountMeta::new(params.mint_y, false),
+            AccountMeta::new(params.winner_ata_x, false),
+            AccountMeta::new(params.maker_ata_y, false),
+            AccountMeta::new(vault, false),
+            AccountMeta::new(bid_vault, false),
+            AccountMeta::new(escrow, false),
+            AccountMeta::new_readonly(params.token_program_x, false),
+            AccountMeta::new_readonly(params.token_program_y, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+            AccountMeta::new_readonly(config_address().0, false),
+            AccountMeta::new(params.treasury, false),
+        ],
+    )
+}
+
+/// Parameters of [`match_ix`], crossing escrow A offering mint_x for mint_y with
+/// escrow B offering mint_y for mint_x.
+pub struct MatchParams {
+    pub matcher: Pubkey,
+    pub maker_a: Pubkey,
+    pub seed_a: u64,
+    pub maker_b: Pubkey,
+    pub seed_b: u64,
+    pub mint_x: Pubkey,
+    pub mint_y: Pubkey,
+    pub maker_a_ata_y: Pubkey,
+    pub maker_b_ata_x: Pubkey,
+    /// Token accounts of the matcher receiving the surplus.
+    pub matcher_ata_x: Pubkey,
+    pub matcher_ata_y: Pubkey,
+    /// Token program owning `mint_x`.
+    pub token_program_x: Pubkey,
+    /// Token program owning `mint_y`.
+    pub token_program_y: Pubkey,
+    /// Token accounts of the config treasury receiving the protocol fee on each side.
+    /// Not used while the fee is zero.
+    pub treasury_x: Pubkey,
+    pub treasury_y: Pubkey,
+    /// Amount of mint_y paid to maker A.
+    pub fill_a: u64,
+    /// Amount of mint_x paid to maker B.
+    pub fill_b: u64,
+}
+
+/// Builds a `Match` instruction, deriving both escrows and their vaults.
+pub fn match_ix(params: &MatchParams) -> Instruction {
+    let (escrow_a, _) = escrow_address(&params.maker_a, params.seed_a);
+    let (escrow_b, _) = escrow_address(&params.maker_b, params.seed_b);
+
+    let mut data = vec![EscrowInstructions::Match as u8];
+    data.extend_from_slice(&params.fill_a.to_le_bytes());
+    data.extend_from_slice(&params.fill_b.to_le_bytes());
+
+    Instruction::new_with_bytes(
+        ID,
+        &data,
+        vec![
+            AccountMeta::new(params.matcher, true),
+            AccountMeta::new(params.maker_a, false),
+            AccountMeta::new(params.maker_b, false),
+            AccountMeta::new(params.mint_x, false),
+            AccountMeta::new(params.mint_y, false),
+            AccountMeta::new(escrow_a, false),
+            AccountMeta::new(escrow_b, false),
+            AccountMeta::new(vault_address(&escrow_a, &params.mint_x).0, false),
+            AccountMeta::new(vault_address(&escrow_b, &params.mint_y).0, false),
+            AccountMeta::new(params.maker_a_ata_y, false),
+            AccountMeta::new(params.maker_b_ata_x, false),
+            AccountMeta::new(params.matcher_ata_x, false),
+            AccountMeta::new(params.matcher_ata_y, false),
+            AccountMeta::new_readonly(params.token_program_x, false),
+            AccountMeta::new_readonly(params.token_program_y, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+            AccountMeta::new_readonly(config_address().0, false),
+            AccountMeta::new(params.treasury_x, false),
+            AccountMeta::new(params.treasury_y, false),
+        ],
+    )
+}
+
+/// Builds a `Dispute` instruction on the escrow of `maker` for `seed`, signed by
+/// `party`, the maker or the designated taker.
+pub fn dispute_ix(party: &Pubkey, maker: &Pubkey, seed: u64) -> Instruction {
+    let (escrow, _) = escrow_address(maker, seed);
+
+    Instruction::new_with_bytes(
+        ID,
+        &[EscrowInstructions::Dispute as u8],
+        vec![
+            AccountMeta::new_readonly(*party, true),
+            AccountMeta::new(escrow, false),
+        ],
+    )
+}
+
+/// Parameters of [`resolve_ix`]. The token accounts and token program are not used
+/// when the escrow offers lamports and can be set to the system program.
+pub struct ResolveParams {
+    pub arbiter: Pubkey,
+    pub maker: Pubkey,
+    pub taker: Pubkey,
+    pub seed: u64,
+    pub mint_x: Pubkey,
+    pub maker_ata_x: Pubkey,
+    pub taker_ata_x: Pubkey,
+    /// Token program owning `mint_x`.
+    pub token_program: Pubkey,
+    pub resolution: Resolution,
+    /// Whether the escrow is a `MULTISIG` escrow, closing its approvals.
+    pub multisig: bool,
+}
+
+/// Builds a `Resolve` instruction settling a dispute, deriving the escrow and its vault.
+pub fn resolve_ix(params: &ResolveParams) -> Instruction {
+    let (escrow, _) = escrow_address(&params.maker, params.seed);
+    let (vault, _) = vault_or_system_program(&escrow, &params.mint_x);
+
+    let (kind, amount) = match params.resolution {
+        Resolution::Release => (0, 0),
+        Resolution::Refund => (1, 0),
+        Resolution::Split(amount) => (2, amount),
+    };
+    let mut data = Vec::with_capacity(1 + ResolveArgs::LEN);
+    data.push(EscrowInstructions::Resolve as u8);
+    data.push(kind);
+    data.extend_from_slice(&amount.to_le_bytes());
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(params.arbiter, true),
+        AccountMeta::new(params.maker, false),
+        AccountMeta::new(params.taker, false),
+        AccountMeta::new(params.mint_x, false),
+        AccountMeta::new(params.maker_ata_x, false),
+        AccountMeta::new(params.taker_ata_x, false),
+        AccountMeta::new(vault, false),
+        AccountMeta::new(escrow, false),
+        AccountMeta::new_readonly(params.token_program, false),
+        AccountMeta::new_readonly(system_program::ID, false),
+    ];
+    if params.multisig {
+        accounts.push(AccountMeta::new(approvals_address(&escrow).0, false));
+    }
+
+    Instruction::new_with_bytes(ID, &data, accounts)
+}
+
+/// Parameters of [`init_approvals_ix`].
+pub struct InitApprovalsParams {
+    pub maker: Pubkey,
+    pub seed: u64,
+    /// Number of approvals Take needs before releasing the vault.
+    pub threshold: u8,
+    /// Up to `MAX_APPROVERS` distinct approvers.
+    pub approvers: Vec<Pubkey>,
+}
+
+/// Builds an `InitApprovals` instruction turning the escrow into a multisig escrow,
+/// deriving the escrow and its approvals.
+pub fn init_approvals_ix(params: &InitApprovalsParams) -> Instruction {
+    let (escrow, _) = escrow_address(&params.maker, params.seed);
+    let (approvals, bump) = approvals_address(&escrow);
+
+    let mut data = Vec::with_capacity(1 + InitApprovalsArgs::HEADER_LEN + params.approvers.len() * 32);
+    data.push(EscrowInstructions::InitApprovals as u8);
+    data.push(bump);
+    data.push(params.threshold);
+    data.push(params.approvers.len() as u8);
+    for approver in &params.approvers {
+        data.extend_from_slice(approver.as_ref());
+    }
+
+    Instruction::new_with_bytes(
+        ID,
+        &data,
+        vec![
+            AccountMeta::new(params.maker, true),
+            AccountMeta::new(escrow, false),
+            AccountMeta::new(approvals, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+/// Builds an `Approve` instruction approving the terms at `version` of the escrow
+/// of `maker` for `seed`, signed by `approver`.
+pub fn approve_ix(approver: &Pubkey, maker: &Pubkey, seed: u64, version: u64) -> Instruction {
+    let (escrow, _) = escrow_address(maker, seed);
+
+    let mut data = vec![EscrowInstructions::Approve as u8];
+    data.extend_from_slice(&version.to_le_bytes());
+
+    Instruction::new_with_bytes(
+        ID,
+        &data,
+        vec![
+            AccountMeta::new_readonly(*approver, true),
+            AccountMeta::new_readonly(escrow, false),
+            AccountMeta::new(approvals_address(&escrow).0, false),
+        ],
+    )
+}
+
+/// Parameters of [`init_config_ix`].
+pub struct InitConfigParams {
+    /// Upgrade authority of the program, which becomes the admin.
+    pub admin: Pubkey,
+    /// Protocol fee taken out of every Take payment, in basis points.
+    pub fee_bps: u16,
+    /// Highest referrer fee a taker can grant, in basis points.
+    pub max_referrer_bps: u16,
+    /// Owner of the token accounts receiving the protocol fee.
+    pub treasury: Pubkey,
+}
+
+/// Builds an `InitConfig` instruction, deriving the config and the program data account.
+pub fn init_config_ix(params: &InitConfigParams) -> Instruction {
+    let (config, bump) = config_address();
+
+    let mut data = Vec::with_capacity(1 + InitConfigArgs::LEN);
+    data.push(EscrowInstructions::InitConfig as u8);
+    data.push(bump);
+    data.extend_from_slice(&params.fee_bps.to_le_bytes());
+    data.extend_from_slice(&params.max_referrer_bps.to_le_bytes());
+    data.extend_from_slice(params.treasury.as_ref());
+
+    Instruction::new_with_bytes(
+        ID,
+        &data,
+        vec![
+            AccountMeta::new(params.admin, true),
+            AccountMeta::new(config, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+            AccountMeta::new_readonly(bpf_loader_upgradeable::get_program_data_address(&ID), false),
+        ],
+    )
+}
+
+/// Builds a `SetPaused` instruction pausing or resuming makes and takes, signed by the admin.
+pub fn set_paused_ix(admin: &Pubkey, paused: bool) -> Instruction {
+    Instruction::new_with_bytes(
+        ID,
+        &[EscrowInstructions::SetPaused as u8, paused as u8],
+        vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(config_address().0, false),
+        ],
+    )
+}
+
+/// Builds a `SetAdmin` instruction handing the config over to `new_admin`, signed by the current admin.
+pub fn set_admin_ix(admin: &Pubkey, new_admin: &Pubkey) -> Instruction {
+    let mut data = vec![EscrowInstructions::SetAdmin as u8];
+    data.extend_from_slice(new_admin.as_ref());
+
+    Instruction::new_with_bytes(
+        ID,
+        &data,
+        vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(config_address().0, false),
+        ],
+    )
+}
+
+/// Parameters of [`update_ix`], the new terms of the escrow.
+pub struct UpdateParams {
+    pub maker: Pubkey,
+    pub seed: u64,
+    /// Amount of mint_y requested for the remaining deposit.
+    pub amount: u64,
+    /// Unix timestamp after which the offer can't be taken, `0` for none.
+    pub expiry: i64,
+    /// Only taker allowed to fill the offer, `None` for anyone.
+    pub taker: Option<Pubkey>,
+}
+
+/// Builds an `Update` instruction, deriving the escrow.
+pub fn update_ix(params: &UpdateParams) -> Instruction {
+    let (escrow, _) = escrow_address(&params.maker, params.seed);
+
+    let mut data = Vec::with_capacity(1 + UpdateArgs::LEN);
+    data.push(EscrowInstructions::Update as u8);
+    data.extend_from_slice(&params.amount.to_le_bytes());
+    data.extend_from_slice(&params.expiry.to_le_bytes());
+    data.extend_from_slice(params.taker.unwrap_or_default().as_ref());
+
+    Instruction::new_with_bytes(
+        ID,
+        &data,
+        vec![
+            AccountMeta::new_readonly(params.maker, true),
+            AccountMeta::new(escrow, false),
+        ],
+    )
+}
+
+/// Parameters of [`deposit_ix`] and [`withdraw_ix`].
+pub struct ResizeParams {
+    pub maker: Pubkey,
+    pub seed: u64,
+    pub mint_x: Pubkey,
+    pub maker_ata_x: Pubkey,
+    /// Token program owning `mint_x`.
+    pub token_program: Pubkey,
+    /// Amount of mint_x moved into or out of the vault.
+    pub amount: u64,
+}
+
+/// Builds a `Deposit` instruction adding to the deposit, deriving the escrow and its vault.
+pub fn deposit_ix(params: &ResizeParams) -> Instruction {
+    resize_ix(EscrowInstructions::Deposit, params)
+}
+
+/// Builds a `Withdraw` instruction taking out of the deposit, deriving the escrow and its vault.
+pub fn withdraw_ix(params: &ResizeParams) -> Instruction {
+    resize_ix(EscrowInstructions::Withdraw, params)
+}
+
+fn resize_ix(instruction: EscrowInstructions, params: &ResizeParams) -> Instruction {
+    let (escrow, _) = escrow_address(&params.maker, params.seed);
+    let (vault, _) = vault_or_system_program(&escrow, &params.mint_x);
+
+    let mut data = vec![instruction as u8];
+    data.extend_from_slice(&params.amount.to_le_bytes());
+
+    Instruction::new_with_bytes(
+        ID,
+        &data,
+        vec![
+            AccountMeta::new(params.maker, true),
+            AccountMeta::new_readonly(params.mint_x, false),
+            AccountMeta::new(params.maker_ata_x, false),
+            AccountMeta::new(vault, false),
+            AccountMeta::new(escrow, false),
+            AccountMeta::new_readonly(params.token_program, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+/// A mint deposited into a basket by [`make_basket_ix`].
+pub struct BasketDeposit {
+    pub mint: Pubkey,
+    pub maker_ata: Pubkey,
+    /// Token program owning `mint`.
+    pub token_program: Pubkey,
+    pub amount: u64,
+}
+
+/// A mint requested by a basket.
+pub struct BasketRequest {
+    pub mint: Pubkey,
+    pub amount: u64,
+}
+
+/// Parameters of [`make_basket_ix`]. Each side holds between one and
+/// `MAX_BASKET_LEGS` mints.
+pub struct MakeBasketParams {
+    pub maker: Pubkey,
+    pub seed: u64,
+    /// Unix timestamp after which the basket can't be taken, `0` for none.
+    pub expiry: i64,
+    /// Only taker allowed to take the basket, `None` for anyone.
+    pub taker: Option<Pubkey>,
+    pub offered: Vec<BasketDeposit>,
+    pub requested: Vec<BasketRequest>,
+}
+
+/// Builds a `MakeBasket` instruction, deriving the basket and the vault of each offered mint.
+pub fn make_basket_ix(params: &MakeBasketParams) -> Instruction {
+    let (basket, bump) = basket_address(&params.maker, params.seed);
+
+    let mut data = Vec::with_capacity(1 + MakeBasketArgs::HEADER_LEN);
+    data.push(EscrowInstructions::MakeBasket as u8);
+    data.push(bump);
+    data.extend_from_slice(&params.seed.to_le_bytes());
+    data.extend_from_slice(&params.expiry.to_le_bytes());
+    data.extend_from_slice(params.taker.unwrap_or_default().as_ref());
+    data.push(params.offered.len() as u8);
+    data.push(params.requested.len() as u8);
+
+    let mut accounts = vec![
+        AccountMeta::new(params.maker, true),
+        AccountMeta::new(basket, false),
+        AccountMeta::new_readonly(system_program::ID, false),
+        AccountMeta::new_readonly(config_address().0, false),
+    ];
+
+    for leg in &params.offered {
+        let (vault, vault_bump) = vault_address(&basket, &leg.mint);
+
+        data.extend_from_slice(&leg.amount.to_le_bytes());
+        data.push(vault_bump);
+
+        accounts.extend([
+            AccountMeta::new_readonly(leg.mint, false),
+            AccountMeta::new(leg.maker_ata, false),
+            AccountMeta::new(vault, false),
+            AccountMeta::new_readonly(leg.token_program, false),
+        ]);
+    }
+
+    for leg in &params.requested {
+        data.extend_from_slice(&leg.amount.to_le_bytes());
+
+        accounts.push(AccountMeta::new_readonly(leg.mint, false));
+    }
+
+    Instruction::new_with_bytes(ID, &data, accounts)
+}
+
+/// A mint received out of a basket vault, by the taker on take or the maker on refund.
+pub struct BasketReceipt {
+    pub mint: Pubkey,
+    /// Token account receiving the vault balance.
+    pub token_account: Pubkey,
+    /// Token program owning `mint`.
+    pub token_program: Pubkey,
+}
+
+/// A mint paid by the taker to the maker of a basket.
+pub struct BasketPayment {
+    pub mint: Pubkey,
+    pub taker_ata: Pubkey,
+    pub maker_ata: Pubkey,
+    /// Token account of the config treasury for `mint` receiving the protocol fee.
+    /// Not used while the fee is zero.
+    pub treasury: Pubkey,
+    /// Token program owning `mint`.
+    pub token_program: Pubkey,
+}
+
+/// Parameters of [`take_basket_ix`]. Legs are given in the order the basket lists them.
+pub struct TakeBasketParams {
+    pub taker: Pubkey,
+    pub maker: Pubkey,
+    pub seed: u64,
+    pub requested: Vec<BasketPayment>,
+    pub offered: Vec<BasketReceipt>,
+}
+
+/// Builds a `TakeBasket` instruction, deriving the basket and its vaults.
+pub fn take_basket_ix(params: &TakeBasketParams) -> Instruction {
+    let (basket, _) = basket_address(&params.maker, params.seed);
+
+    let mut accounts = vec![
+        AccountMeta::new(params.taker, true),
+        AccountMeta::new(params.maker, false),
+        AccountMeta::new(basket, false),
+        AccountMeta::new_readonly(system_program::ID, false),
+        AccountMeta::new_readonly(config_address().0, false),
+    ];
+
+    for leg in &params.requested {
+        accounts.extend([
+            AccountMeta::new_readonly(leg.mint, false),
+            AccountMeta::new(leg.taker_ata, false),
+            AccountMeta::new(leg.maker_ata, false),
+            AccountMeta::new(leg.treasury, false),
+            AccountMeta::new_readonly(leg.token_program, false),
+        ]);
+    }
+
+    for leg in &params.offered {
+        let (vault, _) = vault_address(&basket, &leg.mint);
+
+        accounts.extend([
+            AccountMeta::new(leg.mint, false),
+            AccountMeta::new(vault, false),
+            AccountMeta::new(leg.token_account, false),
+            AccountMeta::new_readonly(leg.token_program, false),
+        ]);
+    }
+
+    Instruction::new_with_bytes(ID, &[EscrowInstructions::TakeBasket as u8], accounts)
+}
+
+/// Parameters of [`refund_basket_ix`]. Legs are given in the order the basket lists them.
+pub struct RefundBasketParams {
+    pub maker: Pubkey,
+    pub seed: u64,
+    pub offered: Vec<BasketReceipt>,
+}
+
+/// Builds a `RefundBasket` instruction, deriving the basket and its vaults.
+pub fn refund_basket_ix(params: &RefundBasketParams) -> Instruction {
+    let (basket, _) = basket_address(&params.maker, params.seed);
+
+    let mut accounts = vec![
+        AccountMeta::new(params.maker, true),
+        AccountMeta::new(basket, false),
+        AccountMeta::new_readonly(system_program::ID, false),
+    ];
+
+    for leg in &params.offered {
+        let (vault, _) = vault_address(&basket, &leg.mint);
+
+        accounts.extend([
+            AccountMeta::new(leg.mint, false),
+            AccountMeta::new(leg.token_account, false),
+            AccountMeta::new(vault, false),
+            AccountMeta::new_readonly(leg.token_program, false),
+        ]);
+    }
+
+    Instruction::new_with_bytes(ID, &[EscrowInstructions::RefundBasket as u8], accounts)
+}
+
+// Escrows offering lamports have no vault, the system program is passed in its place.
+fn vault_or_system_program(escrow: &Pubkey, mint_x: &Pubkey) -> (Pubkey, u8) {
+    if mint_x == &system_program::ID {
+        (system_program::ID, 0)
+    } else {
+        vault_address(escrow, mint_x)
+    }
+}
+
+// The system program stands in for the bid vault of lamport bids
+fn bid_vault_or_system_program(escrow: &Pubkey, mint_y: &Pubkey) -> (Pubkey, u8) {
+    if mint_y == &system_program::ID {
+        (system_program::ID, 0)
+    } else {
+        bid_vault_address(escrow, mint_y)
+    }
+}
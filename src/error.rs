@@ -0,0 +1,102 @@
+use pinocchio::program_error::ProgramError;
+
+/// Errors returned by the escrow program, surfaced to clients as `ProgramError::Custom`
+/// with the discriminant as the error code. Codes are stable: new variants are only
+/// ever appended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EscrowError {
+    /// The escrow account is not the PDA derived from its maker, seed and bump.
+    InvalidEscrowAddress = 0,
+    /// The vault is not the PDA derived from its escrow, mint and bump.
+    InvalidVaultAddress = 1,
+    /// The escrow account has the wrong size or is not owned by the program.
+    InvalidEscrowAccount = 2,
+    /// A mint passed in does not match the one recorded in the escrow.
+    MintMismatch = 3,
+    /// The token program passed in does not own the mint it is used for.
+    InvalidTokenProgram = 4,
+    /// A token account is not owned by the expected wallet or holds another mint.
+    InvalidTokenAccount = 5,
+    /// The expiry given to Make is already in the past.
+    InvalidExpiry = 6,
+    /// The escrow has expired and can only be refunded.
+    EscrowExpired = 7,
+    /// The escrow is private and the signer is not its designated taker.
+    UnauthorizedTaker = 8,
+    /// The fill is zero or larger than the amount left in the escrow.
+    InvalidFillAmount = 9,
+    /// The fill is too small to release any of the deposit.
+    FillTooSmall = 10,
+    /// The escrow terms were updated after the taker built the transaction.
+    TermsChanged = 11,
+    /// An amount is zero, or rescaling it overflows.
+    InvalidAmount = 12,
+    /// A withdrawal would empty the deposit, which only Refund can do.
+    InsufficientDeposit = 13,
+    /// A basket has no mints or more than `MAX_BASKET_LEGS` on one side.
+    InvalidLegCount = 14,
+    /// A mint traded as an NFT doesn't have 0 decimals and a supply of 1.
+    NotAnNft = 15,
+    /// An NFT is traded in an amount other than 1.
+    InvalidNftAmount = 16,
+    /// The metadata account isn't the Metaplex metadata of the mint.
+    InvalidMetadata = 17,
+    /// mint_y isn't a verified member of the requested collection.
+    CollectionMismatch = 18,
+    /// The config account isn't the initialized config PDA.
+    InvalidConfig = 19,
+    /// The protocol fee is over 100%.
+    InvalidFee = 20,
+    /// The admin paused makes and takes, only refunds go through.
+    Paused = 21,
+    /// The signer isn't the admin of the config.
+    UnauthorizedAdmin = 22,
+    /// The referrer fee is above the maximum allowed by the config.
+    InvalidReferrerFee = 23,
+    /// A dispute is open, only the arbiter can settle the escrow.
+    Disputed = 24,
+    /// The escrow has no open dispute to resolve.
+    NotDisputed = 25,
+    /// The escrow has no arbiter, the arbiter is one of the parties or isn't the signer.
+    InvalidArbiter = 26,
+    /// The signer is neither the maker nor the designated taker.
+    NotAParty = 27,
+    /// The approvals account isn't the one of the escrow, or its approvers or threshold are invalid.
+    InvalidApprovals = 28,
+    /// The signer isn't one of the escrow approvers.
+    NotAnApprover = 29,
+    /// Fewer approvers than the threshold approved the escrow terms.
+    ApprovalsPending = 30,
+    /// The instruction doesn't apply to this kind of escrow.
+    WrongEscrowType = 31,
+    /// The escrow needs a designated taker to release the vault to.
+    InvalidRecipient = 32,
+    /// The preimage doesn't hash to the escrow hashlock.
+    InvalidPreimage = 33,
+    /// The escrow can't be refunded before its timelock.
+    TimelockActive = 34,
+    /// The vesting schedule doesn't end after its start, or its cliff is outside of it.
+    InvalidSchedule = 35,
+    /// Nothing more has vested since the last claim.
+    NothingToClaim = 36,
+    /// The auction has no end time or minimum increment, or isn't bid on in fungible tokens.
+    InvalidAuction = 37,
+    /// The bid is under the reserve price or doesn't raise the highest bid by the minimum increment.
+    BidTooLow = 38,
+    /// The auction hasn't ended yet.
+    AuctionActive = 39,
+    /// The auction received no bid, it can only be refunded.
+    NoBids = 40,
+    /// The auction received a bid, it can only be settled.
+    AuctionHasBids = 41,
+    /// The matched escrows don't cross: one releases less than the other asks for.
+    PriceMismatch = 42,
+    /// The escrow has no expiry or hasn't reached it yet.
+    NotExpired = 43,
+}
+
+impl From<EscrowError> for ProgramError {
+    fn from(e: EscrowError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
@@ -0,0 +1,18 @@
+/// SHA-256 of `data`, computed with the `sol_sha256` syscall.
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    #[cfg(target_os = "solana")]
+    {
+        let mut hash = [0u8; 32];
+        let vals = [data];
+        unsafe {
+            pinocchio::syscalls::sol_sha256(vals.as_ptr() as *const u8, vals.len() as u64, hash.as_mut_ptr());
+        }
+        hash
+    }
+
+    #[cfg(not(target_os = "solana"))]
+    {
+        core::hint::black_box(data);
+        panic!("sha256 is only available on target `solana`")
+    }
+}
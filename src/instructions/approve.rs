@@ -0,0 +1,68 @@
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, ProgramResult};
+use pinocchio_log::log;
+
+use super::read_bytes;
+use crate::{error::EscrowError, state::{Approvals, Escrow}};
+
+/// Instruction data of `Approve`.
+pub struct ApproveArgs {
+    /// Version of the escrow terms the approver agreed to.
+    pub version: u64,
+}
+
+impl ApproveArgs {
+    pub const LEN: usize = 8;
+}
+
+impl TryFrom<&[u8]> for ApproveArgs {
+    type Error = ProgramError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        if data.len() != Self::LEN {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(ApproveArgs {
+            version: u64::from_le_bytes(read_bytes(data, 0)?),
+        })
+    }
+}
+
+/// Records the approval of the signer in the bitmap of a multisig escrow.
+pub fn process_approve_instruction(accounts: &[AccountInfo], args: ApproveArgs) -> ProgramResult {
+    let [approver, escrow, approvals] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys)
+    };
+
+    if !approver.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature)
+    }
+
+    let escrow_account = Escrow::from_account_info(escrow)?;
+
+    escrow_account.check_address(escrow.key(), &escrow_account.maker)?;
+
+    let approvals_account = Approvals::from_account_info(approvals, escrow.key())?;
+
+    // The maker may have updated the terms since the approver reviewed them
+    if args.version != escrow_account.version {
+        return Err(EscrowError::TermsChanged.into())
+    }
+
+    // Approvals of previous terms don't count towards the new ones
+    if approvals_account.version != escrow_account.version {
+        approvals_account.version = escrow_account.version;
+        approvals_account.approved = 0;
+    }
+
+    let index = approvals_account
+        .approvers()
+        .iter()
+        .position(|key| key == approver.key())
+        .ok_or(EscrowError::NotAnApprover)?;
+    approvals_account.approved |= 1 << index;
+
+    log!("Approvals: {} of {}", approvals_account.approved.count_ones(), approvals_account.threshold);
+
+    Ok(())
+}
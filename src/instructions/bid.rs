@@ -0,0 +1,154 @@
+use pinocchio::{account_info::AccountInfo, instruction::{Seed, Signer}, program_error::ProgramError, pubkey::Pubkey, sysvars::{clock::Clock, Sysvar}, ProgramResult};
+use pinocchio_log::log;
+
+use super::read_bytes;
+use crate::{error::EscrowError, state::Escrow, token::{self, TransferChecked}};
+
+/// Instruction data of `Bid`.
+pub struct BidArgs {
+    /// Amount of mint_y bid.
+    pub amount: u64,
+}
+
+impl BidArgs {
+    pub const LEN: usize = 8;
+}
+
+impl TryFrom<&[u8]> for BidArgs {
+    type Error = ProgramError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        if data.len() != Self::LEN {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(BidArgs {
+            amount: u64::from_le_bytes(read_bytes(data, 0)?),
+        })
+    }
+}
+
+/// Places a bid on an auction, moving it into the bid vault and refunding the
+/// previous highest bid to its bidder in the same instruction.
+pub fn process_bid_instruction(accounts: &[AccountInfo], args: BidArgs) -> ProgramResult {
+    let [
+        bidder, maker, mint_y, bidder_ata_y, bid_vault, escrow, previous_bidder, previous_bidder_ata_y, token_program_y, _system_program,
+        _config,
+    ] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys)
+    };
+
+    if !bidder.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature)
+    }
+
+    let escrow_account = Escrow::from_account_info(escrow)?;
+    let Some(auction) = escrow_account.auction(escrow) else {
+        return Err(EscrowError::WrongEscrowType.into())
+    };
+    if escrow_account.maker != *maker.key() {
+        return Err(EscrowError::InvalidEscrowAddress.into())
+    }
+    if escrow_account.mint_y != *mint_y.key() {
+        return Err(EscrowError::MintMismatch.into())
+    }
+
+    escrow_account.check_address(escrow.key(), maker.key())?;
+
+    let seed = escrow_account.seed.to_le_bytes();
+    let bump = [escrow_account.bump];
+
+    // Private auctions can only be bid on by the taker chosen by the maker
+    if escrow_account.taker != Pubkey::default() && escrow_account.taker != *bidder.key() {
+        return Err(EscrowError::UnauthorizedTaker.into())
+    }
+    if Clock::get()?.unix_timestamp >= escrow_account.expiry {
+        return Err(EscrowError::EscrowExpired.into())
+    }
+
+    // The first bid has to meet the reserve price, later ones to raise the highest bid
+    let has_bid = auction.has_bid();
+    let min_bid = if has_bid {
+        auction.bid.checked_add(auction.min_increment).ok_or(ProgramError::ArithmeticOverflow)?
+    } else {
+        escrow_account.amount.max(1)
+    };
+    if has_bid && auction.bidder != *previous_bidder.key() {
+        return Err(EscrowError::InvalidRecipient.into())
+    }
+
+    let bid = if escrow_account.requests_sol() {
+        if args.amount < min_bid {
+            return Err(EscrowError::BidTooLow.into())
+        }
+
+        // Lamport bids are held by the escrow account, on top of its rent and deposit
+        pinocchio_system::instructions::Transfer{
+            from: bidder,
+            to: escrow,
+            lamports: args.amount,
+        }.invoke()?;
+
+        if has_bid {
+            unsafe {
+                *escrow.borrow_mut_lamports_unchecked() -= auction.bid;
+                *previous_bidder.borrow_mut_lamports_unchecked() += auction.bid;
+            }
+        }
+
+        args.amount
+    } else {
+        auction.check_bid_vault(bid_vault.key(), escrow.key(), mint_y.key())?;
+        if token_program_y.key() != mint_y.owner() {
+            return Err(EscrowError::InvalidTokenProgram.into())
+        }
+
+        let decimals = token::mint(mint_y)?.decimals();
+        let before = token::token_account(bid_vault)?.amount();
+
+        TransferChecked{
+            from: bidder_ata_y,
+            mint: mint_y,
+            to: bid_vault,
+            authority: bidder,
+            amount: args.amount,
+            decimals,
+            token_program: token_program_y.key(),
+        }.invoke()?;
+
+        // Token-2022 transfer fees are withheld in the bid vault, so only count what it received
+        let received = token::token_account(bid_vault)?.amount() - before;
+        if received < min_bid {
+            return Err(EscrowError::BidTooLow.into())
+        }
+
+        if has_bid {
+            // The outbid tokens have to go back to an account of the previous bidder
+            let previous_bidder_ata_y_account = token::token_account(previous_bidder_ata_y)?;
+            if previous_bidder_ata_y_account.owner() != previous_bidder.key() || previous_bidder_ata_y_account.mint() != mint_y.key() {
+                return Err(EscrowError::InvalidTokenAccount.into())
+            }
+
+            let signer_seeds = [Seed::from(b"escrow"), Seed::from(maker.key()), Seed::from(&seed), Seed::from(&bump)];
+
+            TransferChecked{
+                from: bid_vault,
+                mint: mint_y,
+                to: previous_bidder_ata_y,
+                authority: escrow,
+                amount: auction.bid,
+                decimals,
+                token_program: token_program_y.key(),
+            }.invoke_signed(&[Signer::from(&signer_seeds)])?;
+        }
+
+        received
+    };
+
+    auction.bidder = *bidder.key();
+    auction.bid = bid;
+
+    log!("Bid: {}", bid);
+
+    Ok(())
+}
@@ -0,0 +1,114 @@
+use pinocchio::{account_info::AccountInfo, instruction::{Seed, Signer}, program_error::ProgramError, sysvars::{clock::Clock, Sysvar}, ProgramResult};
+use pinocchio_log::log;
+
+use super::read_bytes;
+use crate::{error::EscrowError, hash, state::Escrow, token::{self, TransferChecked}};
+
+/// Instruction data of `Claim`.
+pub struct ClaimArgs {
+    /// Secret whose SHA-256 is the escrow hashlock.
+    pub preimage: [u8; 32],
+}
+
+impl ClaimArgs {
+    pub const LEN: usize = 32;
+}
+
+impl TryFrom<&[u8]> for ClaimArgs {
+    type Error = ProgramError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        if data.len() != Self::LEN {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(ClaimArgs {
+            preimage: read_bytes(data, 0)?,
+        })
+    }
+}
+
+/// Releases a hash time-locked escrow to its taker against the preimage of its hashlock.
+/// Anyone holding the preimage can send it, which reveals it for the other leg of the swap.
+pub fn process_claim_instruction(accounts: &[AccountInfo], args: ClaimArgs) -> ProgramResult {
+    let [maker, taker, mint_x, taker_ata_x, vault, escrow, token_program, _system_program] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys)
+    };
+
+    let escrow_account = Escrow::from_account_info(escrow)?;
+    let Some(htlc) = escrow_account.htlc(escrow) else {
+        return Err(EscrowError::WrongEscrowType.into())
+    };
+    if escrow_account.is_disputed() {
+        return Err(EscrowError::Disputed.into())
+    }
+    if escrow_account.maker != *maker.key() {
+        return Err(EscrowError::InvalidEscrowAddress.into())
+    }
+    if escrow_account.taker != *taker.key() {
+        return Err(EscrowError::InvalidRecipient.into())
+    }
+    if escrow_account.mint_x != *mint_x.key() {
+        return Err(EscrowError::MintMismatch.into())
+    }
+
+    escrow_account.check_address(escrow.key(), maker.key())?;
+
+    let seed = escrow_account.seed.to_le_bytes();
+    let bump = [escrow_account.bump];
+
+    // Past the timelock the escrow can only be refunded to the maker
+    if Clock::get()?.unix_timestamp >= escrow_account.expiry {
+        return Err(EscrowError::EscrowExpired.into())
+    }
+    if hash::sha256(&args.preimage) != htlc.hashlock {
+        return Err(EscrowError::InvalidPreimage.into())
+    }
+
+    if escrow_account.offers_sol() {
+        let deposit = escrow_account.deposit;
+
+        unsafe {
+            *escrow.borrow_mut_lamports_unchecked() -= deposit;
+            *taker.borrow_mut_lamports_unchecked() += deposit;
+        }
+
+        log!("Claim: {}", deposit);
+    } else {
+        escrow_account.check_vault(vault.key(), escrow.key(), mint_x.key())?;
+        if token_program.key() != mint_x.owner() {
+            return Err(EscrowError::InvalidTokenProgram.into())
+        }
+
+        // Anyone can send the claim, so the vault has to go to an account of the taker
+        let taker_ata_x_account = token::token_account(taker_ata_x)?;
+        if taker_ata_x_account.owner() != taker.key() || taker_ata_x_account.mint() != mint_x.key() {
+            return Err(EscrowError::InvalidTokenAccount.into())
+        }
+
+        let vault_amount = token::token_account(vault)?.amount();
+        let signer_seeds = [Seed::from(b"escrow"), Seed::from(maker.key()), Seed::from(&seed), Seed::from(&bump)];
+
+        TransferChecked{
+            from: vault,
+            mint: mint_x,
+            to: taker_ata_x,
+            authority: escrow,
+            amount: vault_amount,
+            decimals: token::mint(mint_x)?.decimals(),
+            token_program: token_program.key(),
+        }.invoke_signed(&[Signer::from(&signer_seeds)])?;
+
+        token::close_vault(vault, mint_x, maker, escrow, token_program.key(), &[Signer::from(&signer_seeds)])?;
+
+        log!("Claim: {}", vault_amount);
+    }
+
+    // Closing Escrow and sending lamports to maker
+    unsafe{
+        *maker.borrow_mut_lamports_unchecked() += *escrow.borrow_lamports_unchecked();
+        *escrow.borrow_mut_lamports_unchecked() = 0;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,85 @@
+use pinocchio::{account_info::AccountInfo, instruction::{Seed, Signer}, program_error::ProgramError, sysvars::{clock::Clock, Sysvar}, ProgramResult};
+use pinocchio_log::log;
+
+use crate::{error::EscrowError, state::Escrow, token::{self, TransferChecked}};
+
+/// Pays the taker of a vesting escrow what vested since its last claim, closing the
+/// vault and the escrow once everything is paid out. Anyone can crank it.
+pub fn process_claim_vested_instruction(accounts: &[AccountInfo], _data: &[u8]) -> ProgramResult {
+    let [maker, taker, mint_x, taker_ata_x, vault, escrow, token_program, _system_program] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys)
+    };
+
+    let escrow_account = Escrow::from_account_info(escrow)?;
+    let Some(vesting) = escrow_account.vesting(escrow) else {
+        return Err(EscrowError::WrongEscrowType.into())
+    };
+    if escrow_account.maker != *maker.key() {
+        return Err(EscrowError::InvalidEscrowAddress.into())
+    }
+    if escrow_account.taker != *taker.key() {
+        return Err(EscrowError::InvalidRecipient.into())
+    }
+    if escrow_account.mint_x != *mint_x.key() {
+        return Err(EscrowError::MintMismatch.into())
+    }
+
+    escrow_account.check_address(escrow.key(), maker.key())?;
+
+    let seed = escrow_account.seed.to_le_bytes();
+    let bump = [escrow_account.bump];
+
+    let vested = vesting.vested(escrow_account.deposit, Clock::get()?.unix_timestamp);
+    let amount = vested.saturating_sub(vesting.claimed);
+    if amount == 0 {
+        return Err(EscrowError::NothingToClaim.into())
+    }
+    vesting.claimed = vested;
+    let fully_claimed = vested == escrow_account.deposit;
+
+    if escrow_account.offers_sol() {
+        unsafe {
+            *escrow.borrow_mut_lamports_unchecked() -= amount;
+            *taker.borrow_mut_lamports_unchecked() += amount;
+        }
+    } else {
+        escrow_account.check_vault(vault.key(), escrow.key(), mint_x.key())?;
+        if token_program.key() != mint_x.owner() {
+            return Err(EscrowError::InvalidTokenProgram.into())
+        }
+
+        // Anyone can send the claim, so the tokens have to go to an account of the taker
+        let taker_ata_x_account = token::token_account(taker_ata_x)?;
+        if taker_ata_x_account.owner() != taker.key() || taker_ata_x_account.mint() != mint_x.key() {
+            return Err(EscrowError::InvalidTokenAccount.into())
+        }
+
+        let signer_seeds = [Seed::from(b"escrow"), Seed::from(maker.key()), Seed::from(&seed), Seed::from(&bump)];
+
+        TransferChecked{
+            from: vault,
+            mint: mint_x,
+            to: taker_ata_x,
+            authority: escrow,
+            amount,
+            decimals: token::mint(mint_x)?.decimals(),
+            token_program: token_program.key(),
+        }.invoke_signed(&[Signer::from(&signer_seeds)])?;
+
+        if fully_claimed {
+            token::close_vault(vault, mint_x, maker, escrow, token_program.key(), &[Signer::from(&signer_seeds)])?;
+        }
+    }
+
+    log!("ClaimVested: {} of {} claimed", vested, escrow_account.deposit);
+
+    if fully_claimed {
+        // Closing Escrow and sending lamports to maker
+        unsafe{
+            *maker.borrow_mut_lamports_unchecked() += *escrow.borrow_lamports_unchecked();
+            *escrow.borrow_mut_lamports_unchecked() = 0;
+        }
+    }
+
+    Ok(())
+}
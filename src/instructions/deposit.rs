@@ -0,0 +1,98 @@
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, ProgramResult};
+
+use super::read_bytes;
+use crate::{error::EscrowError, state::Escrow, token::{self, TransferChecked}};
+
+/// Instruction data of `Deposit`.
+pub struct DepositArgs {
+    /// Amount of mint_x added to the deposit.
+    pub amount: u64,
+}
+
+impl DepositArgs {
+    pub const LEN: usize = 8;
+}
+
+impl TryFrom<&[u8]> for DepositArgs {
+    type Error = ProgramError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        if data.len() != Self::LEN {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(DepositArgs {
+            amount: u64::from_le_bytes(read_bytes(data, 0)?),
+        })
+    }
+}
+
+pub fn process_deposit_instruction(accounts: &[AccountInfo], args: DepositArgs) -> ProgramResult {
+    let [maker, mint_x, maker_ata_x, vault, escrow, token_program, _system_program] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys)
+    };
+
+    if !maker.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature)
+    }
+
+    let escrow_account = Escrow::from_account_info(escrow)?;
+    if escrow_account.is_disputed() {
+        return Err(EscrowError::Disputed.into())
+    }
+    if !escrow_account.is_trade() {
+        return Err(EscrowError::WrongEscrowType.into())
+    }
+    if escrow_account.mint_x != *mint_x.key() {
+        return Err(EscrowError::MintMismatch.into())
+    }
+
+    escrow_account.check_address(escrow.key(), maker.key())?;
+
+    if args.amount == 0 {
+        return Err(EscrowError::InvalidAmount.into())
+    }
+    // An NFT deposit is already the whole supply of its mint
+    if escrow_account.offers_nft() {
+        return Err(EscrowError::InvalidNftAmount.into())
+    }
+
+    let received = if escrow_account.offers_sol() {
+        pinocchio_system::instructions::Transfer{
+            from: maker,
+            to: escrow,
+            lamports: args.amount,
+        }.invoke()?;
+
+        args.amount
+    } else {
+        escrow_account.check_vault(vault.key(), escrow.key(), mint_x.key())?;
+
+        if token_program.key() != mint_x.owner() {
+            return Err(EscrowError::InvalidTokenProgram.into())
+        }
+
+        let vault_amount = token::token_account(vault)?.amount();
+
+        TransferChecked{
+            from: maker_ata_x,
+            mint: mint_x,
+            to: vault,
+            authority: maker,
+            amount: args.amount,
+            decimals: token::mint(mint_x)?.decimals(),
+            token_program: token_program.key(),
+        }.invoke()?;
+
+        // Token-2022 transfer fees are withheld in the vault, so only count what it actually received
+        token::token_account(vault)?.amount() - vault_amount
+    };
+
+    let deposit = escrow_account.deposit.checked_add(received).ok_or(ProgramError::ArithmeticOverflow)?;
+    escrow_account.resize(escrow_account.dutch(escrow), deposit)?;
+
+    // Takes and approvals of the previous terms are rejected
+    escrow_account.version += 1;
+
+    Ok(())
+}
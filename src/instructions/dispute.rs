@@ -0,0 +1,33 @@
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, ProgramResult};
+
+use crate::{error::EscrowError, state::{Escrow, STATUS_DISPUTED, STATUS_OPEN}};
+
+/// Opens a dispute on an escrow with an arbiter, signed by the maker or the designated
+/// taker. Takes, refunds and changes to the escrow are blocked until the arbiter resolves it.
+pub fn process_dispute_instruction(accounts: &[AccountInfo], _data: &[u8]) -> ProgramResult {
+    let [party, escrow] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys)
+    };
+
+    if !party.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature)
+    }
+
+    let escrow_account = Escrow::from_account_info(escrow)?;
+
+    escrow_account.check_address(escrow.key(), &escrow_account.maker)?;
+
+    if !escrow_account.has_arbiter() {
+        return Err(EscrowError::InvalidArbiter.into())
+    }
+    if *party.key() != escrow_account.maker && *party.key() != escrow_account.taker {
+        return Err(EscrowError::NotAParty.into())
+    }
+    if escrow_account.status != STATUS_OPEN {
+        return Err(EscrowError::Disputed.into())
+    }
+
+    escrow_account.status = STATUS_DISPUTED;
+
+    Ok(())
+}
@@ -0,0 +1,52 @@
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, sysvars::{clock::Clock, Sysvar}, ProgramResult};
+use pinocchio_log::log;
+
+use super::close_to_maker;
+use crate::{error::EscrowError, state::Escrow, token};
+
+/// Lamports paid out of the escrow rent to whoever sends `Expire`.
+pub const EXPIRE_TIP: u64 = 100_000;
+
+/// Refunds an expired escrow to its maker on behalf of anyone, who gets `EXPIRE_TIP`
+/// for the rent it frees. The accounts are the ones of `Refund` after the keeper.
+pub fn process_expire_instruction(accounts: &[AccountInfo], _data: &[u8]) -> ProgramResult {
+    let [keeper, accounts @ ..] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys)
+    };
+    let [maker, mint_x, maker_ata_x, _vault, escrow, ..] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys)
+    };
+
+    if !keeper.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature)
+    }
+
+    let escrow_account = Escrow::from_account_info(escrow)?;
+    if escrow_account.is_disputed() {
+        return Err(EscrowError::Disputed.into())
+    }
+    if escrow_account.is_vesting() {
+        return Err(EscrowError::WrongEscrowType.into())
+    }
+    if escrow_account.expiry == 0 || Clock::get()?.unix_timestamp < escrow_account.expiry {
+        return Err(EscrowError::NotExpired.into())
+    }
+    // The highest bidder is owed the deposit once the auction ends
+    if escrow_account.auction(escrow).is_some_and(|auction| auction.has_bid()) {
+        return Err(EscrowError::AuctionHasBids.into())
+    }
+
+    // The maker doesn't sign, so the deposit has to go back to an account of theirs
+    if !escrow_account.offers_sol() {
+        let maker_ata_x_account = token::token_account(maker_ata_x)?;
+        if maker_ata_x_account.owner() != maker.key() || maker_ata_x_account.mint() != mint_x.key() {
+            return Err(EscrowError::InvalidTokenAccount.into())
+        }
+    }
+
+    close_to_maker(accounts, escrow_account, Some(keeper))?;
+
+    log!("Expire: {} tip", EXPIRE_TIP);
+
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use pinocchio::{account_info::AccountInfo, instruction::{Seed, Signer}, program_error::ProgramError, pubkey::{checked_create_program_address, Pubkey}, sysvars::{rent::Rent, Sysvar}, ProgramResult};
+
+use super::read_bytes;
+use crate::{error::EscrowError, state::{Approvals, Escrow, MAX_APPROVERS, MULTISIG}};
+
+/// Instruction data of `InitApprovals`. Only the first `approvers_len` approvers are used.
+pub struct InitApprovalsArgs {
+    pub bump: u8,
+    /// Number of approvals Take needs before releasing the vault.
+    pub threshold: u8,
+    pub approvers_len: u8,
+    pub approvers: [Pubkey; MAX_APPROVERS],
+}
+
+impl InitApprovalsArgs {
+    /// Length of the data before the approvers.
+    pub const HEADER_LEN: usize = 1 + 1 + 1;
+}
+
+impl TryFrom<&[u8]> for InitApprovalsArgs {
+    type Error = ProgramError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let header: [u8; Self::HEADER_LEN] = read_bytes(data, 0)?;
+        let approvers_len = header[2];
+
+        if !(1..=MAX_APPROVERS).contains(&(approvers_len as usize)) || header[1] == 0 || header[1] > approvers_len {
+            return Err(EscrowError::InvalidApprovals.into());
+        }
+        if data.len() != Self::HEADER_LEN + approvers_len as usize * 32 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let mut approvers = [Pubkey::default(); MAX_APPROVERS];
+        for (i, approver) in approvers.iter_mut().take(approvers_len as usize).enumerate() {
+            *approver = read_bytes(data, Self::HEADER_LEN + i * 32)?;
+        }
+
+        Ok(InitApprovalsArgs {
+            bump: header[0],
+            threshold: header[1],
+            approvers_len,
+            approvers,
+        })
+    }
+}
+
+/// Turns an escrow into a multisig escrow: Take only releases the vault once
+/// `threshold` of the approvers approved its terms.
+pub fn process_init_approvals_instruction(accounts: &[AccountInfo], args: InitApprovalsArgs) -> ProgramResult {
+    let [maker, escrow, approvals, _system_program] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys)
+    };
+
+    if !maker.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature)
+    }
+
+    let escrow_account = Escrow::from_account_info(escrow)?;
+    if !escrow_account.is_trade() {
+        return Err(EscrowError::WrongEscrowType.into())
+    }
+
+    escrow_account.check_address(escrow.key(), maker.key())?;
+
+    let approvals_bump = [args.bump];
+    let approvals_seeds = [(b"approvals"), escrow.key().as_slice(), approvals_bump.as_ref()];
+    let approvals_pda = checked_create_program_address(&approvals_seeds, &crate::ID).map_err(|_| EscrowError::InvalidApprovals)?;
+    if *approvals.key() != approvals_pda {
+        return Err(EscrowError::InvalidApprovals.into())
+    }
+    if approvals.owner() == &crate::ID {
+        return Err(ProgramError::AccountAlreadyInitialized)
+    }
+
+    // Each approver holds a single bit of the bitmap
+    let approvers = &args.approvers[..args.approvers_len as usize];
+    if approvers.iter().enumerate().any(|(i, approver)| approvers[..i].contains(approver)) {
+        return Err(EscrowError::InvalidApprovals.into())
+    }
+
+    let signer_seeds = [Seed::from(b"approvals"), Seed::from(escrow.key()), Seed::from(&approvals_bump)];
+
+    pinocchio_system::instructions::CreateAccount{
+        from: maker,
+        to: approvals,
+        lamports: Rent::get()?.minimum_balance(Approvals::LEN),
+        space: Approvals::LEN as u64,
+        owner: &crate::ID,
+    }.invoke_signed(&[Signer::from(&signer_seeds)])?;
+
+    let approvals_account = Approvals::from_account_info_unchecked(approvals);
+
+    approvals_account.escrow = *escrow.key();
+    approvals_account.approvers = args.approvers;
+    approvals_account.version = escrow_account.version;
+    approvals_account.approvers_len = args.approvers_len;
+    approvals_account.threshold = args.threshold;
+    approvals_account.bump = args.bump;
+
+    escrow_account.flags |= MULTISIG;
+
+    Ok(())
+}
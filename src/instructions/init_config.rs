@@ -0,0 +1,106 @@
+use pinocchio::{account_info::AccountInfo, instruction::{Seed, Signer}, program_error::ProgramError, pubkey::{checked_create_program_address, find_program_address, Pubkey}, sysvars::{rent::Rent, Sysvar}, ProgramResult};
+
+use super::read_bytes;
+use crate::{error::EscrowError, state::Config};
+
+/// Loader of upgradeable programs, owning the program data account of this program.
+pub const BPF_LOADER_UPGRADEABLE_ID: Pubkey = five8_const::decode_32_const("BPFLoaderUpgradeab1e11111111111111111111111");
+
+/// Instruction data of `InitConfig`.
+pub struct InitConfigArgs {
+    pub bump: u8,
+    /// Protocol fee taken out of every Take payment, in basis points.
+    pub fee_bps: u16,
+    /// Highest referrer fee a taker can grant, in basis points.
+    pub max_referrer_bps: u16,
+    /// Owner of the token accounts receiving the protocol fee.
+    pub treasury: Pubkey,
+}
+
+impl InitConfigArgs {
+    pub const LEN: usize = 1 + 2 + 2 + 32;
+}
+
+impl TryFrom<&[u8]> for InitConfigArgs {
+    type Error = ProgramError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        if data.len() != Self::LEN {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(InitConfigArgs {
+            bump: data[0],
+            fee_bps: u16::from_le_bytes(read_bytes(data, 1)?),
+            max_referrer_bps: u16::from_le_bytes(read_bytes(data, 3)?),
+            treasury: read_bytes(data, 5)?,
+        })
+    }
+}
+
+/// Creates the global config with the signer as its admin. The config can only be
+/// initialized once, by the upgrade authority of the program, so nobody can take it
+/// over between the deployment and this instruction.
+pub fn process_init_config_instruction(accounts: &[AccountInfo], args: InitConfigArgs) -> ProgramResult {
+    let [admin, config, _system_program, program_data] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys)
+    };
+
+    if !admin.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature)
+    }
+
+    if program_data.owner() != &BPF_LOADER_UPGRADEABLE_ID
+        || program_data.key() != &find_program_address(&[crate::ID.as_ref()], &BPF_LOADER_UPGRADEABLE_ID).0
+    {
+        return Err(ProgramError::InvalidAccountData)
+    }
+
+    // Program data layout:
+    // -  [0..4]: account type, 3 for program data
+    // -  [4..12]: slot of the last deployment
+    // -  [12]: whether the program has an upgrade authority
+    // -  [13..45]: upgrade authority
+    let data = unsafe { program_data.borrow_data_unchecked() };
+    if data.len() < 45 || data[..4] != 3u32.to_le_bytes() {
+        return Err(ProgramError::InvalidAccountData)
+    }
+    if data[12] != 1 || data[13..45] != *admin.key() {
+        return Err(EscrowError::UnauthorizedAdmin.into())
+    }
+
+    let bump = [args.bump];
+    let pda = checked_create_program_address(&[b"config", bump.as_ref()], &crate::ID).map_err(|_| EscrowError::InvalidConfig)?;
+    if &pda != config.key() {
+        return Err(EscrowError::InvalidConfig.into())
+    }
+
+    if config.owner() == &crate::ID {
+        return Err(ProgramError::AccountAlreadyInitialized)
+    }
+
+    // Both fees together can't take more than the whole payment
+    if args.fee_bps as u32 + args.max_referrer_bps as u32 > 10_000 {
+        return Err(EscrowError::InvalidFee.into())
+    }
+
+    let signer_seeds = [Seed::from(b"config"), Seed::from(&bump)];
+
+    pinocchio_system::instructions::CreateAccount{
+        from: admin,
+        to: config,
+        lamports: Rent::get()?.minimum_balance(Config::LEN),
+        space: Config::LEN as u64,
+        owner: &crate::ID,
+    }.invoke_signed(&[Signer::from(&signer_seeds)])?;
+
+    let config_account = Config::from_account_info_unchecked(config);
+
+    config_account.admin = *admin.key();
+    config_account.treasury = args.treasury;
+    config_account.fee_bps = args.fee_bps;
+    config_account.max_referrer_bps = args.max_referrer_bps;
+    config_account.bump = args.bump;
+
+    Ok(())
+}
@@ -1,59 +1,355 @@
-use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey, sysvars::{rent::Rent, Sysvar}, ProgramResult};
+use pinocchio::{account_info::AccountInfo, instruction::{Seed, Signer}, program_error::ProgramError, pubkey::{self, Pubkey}, sysvars::{clock::Clock, rent::Rent, Sysvar}, ProgramResult};
 use pinocchio_log::log;
-use pinocchio_token::state::TokenAccount;
 
-use crate::state::Escrow;
+use crate::{error::EscrowError, metadata, state::{check_bid_vault_address, check_vault_address, Escrow, AUCTION, DUTCH, HTLC, NFT_X, NFT_Y, VESTING}, token::{self, TransferChecked}};
 
+use super::read_bytes;
 
-pub fn process_make_instruction(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
-    let [maker, mint_x, mint_y, maker_ata, vault, escrow, _system_program, _token_program] = accounts else {
+/// Instruction data of `Make`.
+pub struct MakeArgs {
+    pub bump: u8,
+    pub seed: u64,
+    /// Amount of mint_y requested, the reserve price of an `AUCTION` or the start price of a `DUTCH` escrow.
+    pub amount: u64,
+    /// Amount of mint_x deposited.
+    pub deposit: u64,
+    /// Unix timestamp after which the offer can't be taken, `0` for none. The end of an `AUCTION`.
+    pub expiry: i64,
+    /// Only taker allowed to fill the offer, the default public key for anyone.
+    pub taker: Pubkey,
+    pub vault_bump: u8,
+    /// `NFT_X` and/or `NFT_Y` to trade either side as an NFT, `HTLC` for a hash time-locked
+    /// escrow, `VESTING` for a vesting grant, `AUCTION` for an English auction or `DUTCH` for a Dutch auction.
+    pub flags: u8,
+    /// Collection mint_y has to be a verified member of, the default public key for none.
+    pub collection: Pubkey,
+    /// Third party settling disputes, the default public key for none. Requires a designated taker.
+    pub arbiter: Pubkey,
+    // The fields below follow only for the mode set in `flags`, and are zero otherwise
+    /// SHA-256 of the preimage claiming an `HTLC` escrow.
+    pub hashlock: [u8; 32],
+    /// Unix timestamp a `VESTING` escrow starts unlocking from.
+    pub vesting_start: i64,
+    /// Unix timestamp before which nothing of a `VESTING` escrow is unlocked, `0` for none.
+    pub vesting_cliff: i64,
+    /// Unix timestamp at which a `VESTING` escrow is fully unlocked.
+    pub vesting_end: i64,
+    /// Smallest raise over the highest bid of an `AUCTION`.
+    pub min_increment: u64,
+    /// The bump of the bid vault of an `AUCTION` asking for tokens.
+    pub bid_vault_bump: u8,
+    /// Amount of mint_y a `DUTCH` escrow asks for at the end of its decay.
+    pub dutch_floor: u64,
+    /// Unix timestamp the price of a `DUTCH` escrow starts decaying from.
+    pub dutch_start: i64,
+    /// Unix timestamp at which the price of a `DUTCH` escrow reaches its floor.
+    pub dutch_end: i64,
+    /// Seconds between price drops of a `DUTCH` escrow, `0` for a linear decay.
+    pub dutch_step: i64,
+}
+
+impl MakeArgs {
+    pub const LEN: usize = 1 + 8 + 8 + 8 + 8 + 32 + 1 + 1 + 32 + 32;
+
+    /// Length of the instruction data with `flags`, the arguments above followed by those of its mode.
+    pub fn len(flags: u8) -> usize {
+        Self::LEN + if flags & HTLC != 0 {
+            32
+        } else if flags & VESTING != 0 {
+            8 + 8 + 8
+        } else if flags & AUCTION != 0 {
+            8 + 1
+        } else if flags & DUTCH != 0 {
+            8 + 8 + 8 + 8
+        } else {
+            0
+        }
+    }
+}
+
+impl TryFrom<&[u8]> for MakeArgs {
+    type Error = ProgramError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        // An escrow is a trade, a hash time-locked escrow, a vesting grant or an auction
+        if data.len() < Self::LEN
+            || data[66] & !(NFT_X | NFT_Y | HTLC | VESTING | AUCTION | DUTCH) != 0
+            || (data[66] & (HTLC | VESTING | AUCTION | DUTCH)).count_ones() > 1
+            || data.len() != Self::len(data[66])
+        {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let flags = data[66];
+        let mut args = MakeArgs {
+            bump: data[0],
+            seed: u64::from_le_bytes(read_bytes(data, 1)?),
+            amount: u64::from_le_bytes(read_bytes(data, 9)?),
+            deposit: u64::from_le_bytes(read_bytes(data, 17)?),
+            expiry: i64::from_le_bytes(read_bytes(data, 25)?),
+            taker: read_bytes(data, 33)?,
+            vault_bump: data[65],
+            flags,
+            collection: read_bytes(data, 67)?,
+            arbiter: read_bytes(data, 99)?,
+            hashlock: [0; 32],
+            vesting_start: 0,
+            vesting_cliff: 0,
+            vesting_end: 0,
+            min_increment: 0,
+            bid_vault_bump: 0,
+            dutch_floor: 0,
+            dutch_start: 0,
+            dutch_end: 0,
+            dutch_step: 0,
+        };
+
+        if flags & HTLC != 0 {
+            args.hashlock = read_bytes(data, 131)?;
+        } else if flags & VESTING != 0 {
+            args.vesting_start = i64::from_le_bytes(read_bytes(data, 131)?);
+            args.vesting_cliff = i64::from_le_bytes(read_bytes(data, 139)?);
+            args.vesting_end = i64::from_le_bytes(read_bytes(data, 147)?);
+        } else if flags & AUCTION != 0 {
+            args.min_increment = u64::from_le_bytes(read_bytes(data, 131)?);
+            args.bid_vault_bump = data[139];
+        } else if flags & DUTCH != 0 {
+            args.dutch_floor = u64::from_le_bytes(read_bytes(data, 131)?);
+            args.dutch_start = i64::from_le_bytes(read_bytes(data, 139)?);
+            args.dutch_end = i64::from_le_bytes(read_bytes(data, 147)?);
+            args.dutch_step = i64::from_le_bytes(read_bytes(data, 155)?);
+        }
+
+        Ok(args)
+    }
+}
+
+pub fn process_make_instruction(accounts: &[AccountInfo], args: MakeArgs) -> ProgramResult {
+    let [maker, mint_x, mint_y, maker_ata, vault, escrow, _system_program, token_program, _config, remaining @ ..] = accounts else {
         return Err(ProgramError::NotEnoughAccountKeys)
     };
 
-    let bump = unsafe { *(data.as_ptr() as *const u8)}.to_le_bytes();
-    let seed = [(b"escrow"), maker.key().as_slice(), bump.as_ref()];
-    let seeds = &seed[..];
+    if !maker.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature)
+    }
+
+    let bump = [args.bump];
+    let seed = args.seed.to_le_bytes();
+    let seeds = [(b"escrow"), maker.key().as_slice(), seed.as_ref(), bump.as_ref()];
 
-    let pda = pubkey::checked_create_program_address(seeds, &crate::ID).unwrap();
-    assert_eq!(&pda, escrow.key());
+    let pda = pubkey::checked_create_program_address(&seeds, &crate::ID).map_err(|_| EscrowError::InvalidEscrowAddress)?;
+    if &pda != escrow.key() {
+        return Err(EscrowError::InvalidEscrowAddress.into())
+    }
+
+    if args.expiry != 0 && args.expiry <= Clock::get()?.unix_timestamp {
+        return Err(EscrowError::InvalidExpiry.into())
+    }
+
+    // Disputes are between the maker and a known taker, settled by someone else
+    if args.arbiter != Pubkey::default()
+        && (args.taker == Pubkey::default() || args.arbiter == args.taker || args.arbiter == *maker.key())
+    {
+        return Err(EscrowError::InvalidArbiter.into())
+    }
+
+    // A hash time-locked escrow is released to its taker until the timelock, then refunded
+    if args.flags & HTLC != 0 {
+        if args.expiry == 0 {
+            return Err(EscrowError::InvalidExpiry.into())
+        }
+        if args.taker == Pubkey::default() {
+            return Err(EscrowError::InvalidRecipient.into())
+        }
+    }
+
+    // A vesting grant unlocks between its start and end, to its taker
+    if args.flags & VESTING != 0 {
+        if args.vesting_end <= args.vesting_start
+            || (args.vesting_cliff != 0 && !(args.vesting_start..=args.vesting_end).contains(&args.vesting_cliff))
+        {
+            return Err(EscrowError::InvalidSchedule.into())
+        }
+        if args.taker == Pubkey::default() {
+            return Err(EscrowError::InvalidRecipient.into())
+        }
+        // Grants are irrevocable, there is nothing for an arbiter to settle
+        if args.arbiter != Pubkey::default() {
+            return Err(EscrowError::InvalidArbiter.into())
+        }
+    }
 
-    // checking if mint_x and mint_y are owned by token program so we dont accept any random account
-    assert_eq!(mint_x.owner(), &pinocchio_token::ID);
-    assert_eq!(mint_y.owner(), &pinocchio_token::ID);
+    // An auction ends at its expiry and is bid on in lamports or fungible mint_y tokens
+    if args.flags & AUCTION != 0 {
+        if args.expiry == 0 {
+            return Err(EscrowError::InvalidExpiry.into())
+        }
+        if args.min_increment == 0 || args.flags & NFT_Y != 0 || args.collection != Pubkey::default() {
+            return Err(EscrowError::InvalidAuction.into())
+        }
+        // Bids are only settled after the end, there is nothing for an arbiter to settle
+        if args.arbiter != Pubkey::default() {
+            return Err(EscrowError::InvalidArbiter.into())
+        }
+    }
+
+    // A Dutch auction decays from its start price down to a non-zero floor
+    if args.flags & DUTCH != 0 {
+        if args.dutch_end <= args.dutch_start || args.dutch_step < 0 {
+            return Err(EscrowError::InvalidSchedule.into())
+        }
+        if args.dutch_floor == 0 || args.dutch_floor >= args.amount {
+            return Err(EscrowError::InvalidAmount.into())
+        }
+    }
 
-    // Check if vault is owned (authority) by escrow account
-    assert!( unsafe { TokenAccount::from_account_info_unchecked(vault).unwrap().owner() == escrow.key() } ); 
+    let vault_bump = [args.vault_bump];
+
+    // The system program stands in for the mint of a side paid in lamports
+    let offers_sol = mint_x.key() == &pinocchio_system::ID;
+    let requests_sol = mint_y.key() == &pinocchio_system::ID;
+
+    // checking if mint_x and mint_y are owned by a token program (SPL Token or Token-2022) so we dont accept any random account
+    if !offers_sol {
+        token::mint(mint_x)?;
+
+        // The deposit is moved with the program owning mint_x
+        if token_program.key() != mint_x.owner() {
+            return Err(EscrowError::InvalidTokenProgram.into())
+        }
+
+    }
+    if !requests_sol {
+        token::mint(mint_y)?;
+    }
+
+    // NFTs are traded whole, as the single token of a mint with 0 decimals
+    if args.flags & NFT_X != 0 {
+        if offers_sol || !token::is_nft(mint_x)? {
+            return Err(EscrowError::NotAnNft.into())
+        }
+        if args.deposit != 1 {
+            return Err(EscrowError::InvalidNftAmount.into())
+        }
+    }
+    if args.flags & NFT_Y != 0 {
+        if requests_sol || !token::is_nft(mint_y)? {
+            return Err(EscrowError::NotAnNft.into())
+        }
+        if args.amount != 1 {
+            return Err(EscrowError::InvalidNftAmount.into())
+        }
+    }
+
+    // The Metaplex metadata of mint_y follows the other accounts when a collection is required
+    if args.collection != Pubkey::default() {
+        let [mint_y_metadata, ..] = remaining else {
+            return Err(ProgramError::NotEnoughAccountKeys)
+        };
+
+        if metadata::verified_collection(mint_y_metadata, mint_y.key())? != Some(args.collection) {
+            return Err(EscrowError::CollectionMismatch.into())
+        }
+    }
 
     if escrow.owner() != &crate::ID {
         log!("Creating Escrow Account");
 
-        // Creating Escrow Account
+        let signer_seeds = [Seed::from(b"escrow"), Seed::from(maker.key()), Seed::from(&seed), Seed::from(&bump)];
+
+        // Creating Escrow Account, the escrow PDA signs for its own creation
         pinocchio_system::instructions::CreateAccount{
             from: maker,
             to: escrow,
-            lamports: Rent::get()?.minimum_balance(Escrow::LEN),
-            space: Escrow::LEN as u64,
+            lamports: Rent::get()?.minimum_balance(Escrow::space(args.flags)),
+            space: Escrow::space(args.flags) as u64,
             owner: &crate::ID,
-        }.invoke()?;
+        }.invoke_signed(&[Signer::from(&signer_seeds)])?;
 
         // Populate Escrow Account
-        let escrow_account = Escrow::from_account_info_unchecked(&escrow);
+        let escrow_account = Escrow::from_account_info_unchecked(escrow);
 
         escrow_account.maker = *maker.key();
         escrow_account.mint_x = *mint_x.key();
         escrow_account.mint_y = *mint_y.key();
-        escrow_account.amount = unsafe { *(data.as_ptr().add(1) as *const u64)};
-        escrow_account.bump = unsafe { *data.as_ptr()};
+        escrow_account.taker = args.taker;
+        escrow_account.collection = args.collection;
+        escrow_account.arbiter = args.arbiter;
+        escrow_account.seed = args.seed;
+        escrow_account.amount = args.amount;
+        escrow_account.expiry = args.expiry;
+        escrow_account.bump = bump[0];
+        escrow_account.vault_bump = vault_bump[0];
+        escrow_account.flags = args.flags;
+
+        // The section of the mode follows the escrow
+        if let Some(htlc) = escrow_account.htlc(escrow) {
+            htlc.hashlock = args.hashlock;
+        }
+        if let Some(vesting) = escrow_account.vesting(escrow) {
+            vesting.start = args.vesting_start;
+            vesting.cliff = args.vesting_cliff;
+            vesting.end = args.vesting_end;
+        }
+        if let Some(auction) = escrow_account.auction(escrow) {
+            auction.min_increment = args.min_increment;
+        }
+        if let Some(dutch) = escrow_account.dutch(escrow) {
+            dutch.floor = args.dutch_floor;
+            dutch.start = args.dutch_start;
+            dutch.end = args.dutch_end;
+            dutch.step = args.dutch_step;
+        }
+
+        // Token bids are held by a bid vault at a PDA of the escrow, following the other accounts
+        if let Some(auction) = escrow_account.auction(escrow).filter(|_| !requests_sol) {
+            let [bid_vault, ..] = remaining else {
+                return Err(ProgramError::NotEnoughAccountKeys)
+            };
+
+            check_bid_vault_address(bid_vault.key(), escrow.key(), mint_y.key(), args.bid_vault_bump)?;
+
+            let bid_vault_bump = [args.bid_vault_bump];
+            let bid_vault_seeds = [Seed::from(b"bid_vault"), Seed::from(escrow.key()), Seed::from(mint_y.key()), Seed::from(&bid_vault_bump)];
+            token::create_token_account(maker, bid_vault, mint_y, escrow.key(), &[Signer::from(&bid_vault_seeds)])?;
+
+            auction.bid_vault_bump = bid_vault_bump[0];
+        }
+
+        let deposit = args.deposit;
+
+        log!("Amount: {}", deposit);
+
+        if offers_sol {
+            // Lamports on offer are held by the escrow account itself, on top of its rent
+            pinocchio_system::instructions::Transfer{
+                from: maker,
+                to: escrow,
+                lamports: deposit,
+            }.invoke()?;
+
+            escrow_account.deposit = deposit;
+        } else {
+            // The vault is a token account at a PDA of the escrow, owned by the escrow
+            check_vault_address(vault.key(), escrow.key(), mint_x.key(), args.vault_bump)?;
+
+            let vault_seeds = [Seed::from(b"vault"), Seed::from(escrow.key()), Seed::from(mint_x.key()), Seed::from(&vault_bump)];
+            token::create_token_account(maker, vault, mint_x, escrow.key(), &[Signer::from(&vault_seeds)])?;
 
-        log!("Amount: {}", unsafe{ *(data.as_ptr().add(1 + 8) as *const u64)});
+            // Transfer mint_x (token being offered) from user ata to vault
+            TransferChecked{
+                from: maker_ata,
+                mint: mint_x,
+                to: vault,
+                authority: maker,
+                amount: deposit,
+                decimals: token::mint(mint_x)?.decimals(),
+                token_program: token_program.key(),
+            }.invoke()?;
 
-        // Transfer mint_x (token being offered) from user ata to vault
-        pinocchio_token::instructions::Transfer{
-            from: maker_ata,
-            to: vault,
-            authority: maker,
-            amount: unsafe{ *(data.as_ptr().add(1 + 8) as *const u64)},
-        }.invoke()?;
+            // Token-2022 transfer fees are withheld in the vault, so only record what it actually received
+            escrow_account.deposit = token::token_account(vault)?.amount();
+        }
 
     }
     else{
@@ -61,4 +357,4 @@ pub fn process_make_instruction(accounts: &[AccountInfo], data: &[u8]) -> Progra
     }
 
     Ok(())
-}
\ No newline at end of file
+}
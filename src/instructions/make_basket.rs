@@ -0,0 +1,185 @@
+use pinocchio::{account_info::AccountInfo, instruction::{Seed, Signer}, program_error::ProgramError, pubkey::{self, Pubkey}, sysvars::{clock::Clock, rent::Rent, Sysvar}, ProgramResult};
+
+use super::read_bytes;
+use crate::{error::EscrowError, state::{check_vault_address, Basket, BasketLeg, MAX_BASKET_LEGS}, token::{self, TransferChecked}};
+
+/// Instruction data of `MakeBasket`. Only the first `offered_len` and
+/// `requested_len` entries of the leg arrays are used.
+pub struct MakeBasketArgs {
+    pub bump: u8,
+    pub seed: u64,
+    /// Unix timestamp after which the basket can't be taken, `0` for none.
+    pub expiry: i64,
+    /// Only taker allowed to take the basket, the default public key for anyone.
+    pub taker: Pubkey,
+    pub offered_len: u8,
+    pub requested_len: u8,
+    /// Amount of each offered mint deposited.
+    pub deposits: [u64; MAX_BASKET_LEGS],
+    /// Bump of the vault of each offered mint.
+    pub vault_bumps: [u8; MAX_BASKET_LEGS],
+    /// Amount of each requested mint.
+    pub amounts: [u64; MAX_BASKET_LEGS],
+}
+
+impl MakeBasketArgs {
+    /// Length of the data before the legs.
+    pub const HEADER_LEN: usize = 1 + 8 + 8 + 32 + 1 + 1;
+    /// Length of each offered leg, its deposit and vault bump.
+    pub const OFFERED_LEG_LEN: usize = 8 + 1;
+    /// Length of each requested leg, its amount.
+    pub const REQUESTED_LEG_LEN: usize = 8;
+}
+
+impl TryFrom<&[u8]> for MakeBasketArgs {
+    type Error = ProgramError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let header: [u8; Self::HEADER_LEN] = read_bytes(data, 0)?;
+        let offered_len = header[49];
+        let requested_len = header[50];
+
+        if !(1..=MAX_BASKET_LEGS).contains(&(offered_len as usize)) || !(1..=MAX_BASKET_LEGS).contains(&(requested_len as usize)) {
+            return Err(EscrowError::InvalidLegCount.into());
+        }
+        let len = Self::HEADER_LEN + offered_len as usize * Self::OFFERED_LEG_LEN + requested_len as usize * Self::REQUESTED_LEG_LEN;
+        if data.len() != len {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let mut args = MakeBasketArgs {
+            bump: header[0],
+            seed: u64::from_le_bytes(read_bytes(&header, 1)?),
+            expiry: i64::from_le_bytes(read_bytes(&header, 9)?),
+            taker: read_bytes(&header, 17)?,
+            offered_len,
+            requested_len,
+            deposits: [0; MAX_BASKET_LEGS],
+            vault_bumps: [0; MAX_BASKET_LEGS],
+            amounts: [0; MAX_BASKET_LEGS],
+        };
+
+        let mut offset = Self::HEADER_LEN;
+        for i in 0..offered_len as usize {
+            args.deposits[i] = u64::from_le_bytes(read_bytes(data, offset)?);
+            args.vault_bumps[i] = data[offset + 8];
+            offset += Self::OFFERED_LEG_LEN;
+        }
+        for i in 0..requested_len as usize {
+            args.amounts[i] = u64::from_le_bytes(read_bytes(data, offset)?);
+            offset += Self::REQUESTED_LEG_LEN;
+        }
+
+        Ok(args)
+    }
+}
+
+/// Accounts: maker, basket, system_program, config, followed by `mint, maker_ata, vault, token_program`
+/// for each offered mint and `mint` for each requested mint.
+pub fn process_make_basket_instruction(accounts: &[AccountInfo], args: MakeBasketArgs) -> ProgramResult {
+    let [maker, basket, _system_program, _config, legs @ ..] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys)
+    };
+
+    if !maker.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature)
+    }
+
+    let offered_len = args.offered_len as usize;
+    let requested_len = args.requested_len as usize;
+    if legs.len() != offered_len * 4 + requested_len {
+        return Err(ProgramError::NotEnoughAccountKeys)
+    }
+    let (offered_accounts, requested_accounts) = legs.split_at(offered_len * 4);
+
+    let bump = [args.bump];
+    let seed = args.seed.to_le_bytes();
+    let seeds = [(b"basket"), maker.key().as_slice(), seed.as_ref(), bump.as_ref()];
+
+    let pda = pubkey::checked_create_program_address(&seeds, &crate::ID).map_err(|_| EscrowError::InvalidEscrowAddress)?;
+    if &pda != basket.key() {
+        return Err(EscrowError::InvalidEscrowAddress.into())
+    }
+
+    if args.expiry != 0 && args.expiry <= Clock::get()?.unix_timestamp {
+        return Err(EscrowError::InvalidExpiry.into())
+    }
+
+    if basket.owner() == &crate::ID {
+        return Err(ProgramError::AccountAlreadyInitialized)
+    }
+
+    let signer_seeds = [Seed::from(b"basket"), Seed::from(maker.key()), Seed::from(&seed), Seed::from(&bump)];
+
+    pinocchio_system::instructions::CreateAccount{
+        from: maker,
+        to: basket,
+        lamports: Rent::get()?.minimum_balance(Basket::LEN),
+        space: Basket::LEN as u64,
+        owner: &crate::ID,
+    }.invoke_signed(&[Signer::from(&signer_seeds)])?;
+
+    let basket_account = Basket::from_account_info_unchecked(basket);
+
+    basket_account.maker = *maker.key();
+    basket_account.taker = args.taker;
+    basket_account.seed = args.seed;
+    basket_account.expiry = args.expiry;
+    basket_account.offered_len = args.offered_len;
+    basket_account.requested_len = args.requested_len;
+    basket_account.bump = args.bump;
+
+    for (i, leg) in offered_accounts.chunks_exact(4).enumerate() {
+        let [mint, maker_ata, vault, token_program] = leg else {
+            return Err(ProgramError::NotEnoughAccountKeys)
+        };
+
+        if args.deposits[i] == 0 {
+            return Err(EscrowError::InvalidAmount.into())
+        }
+
+        let decimals = token::mint(mint)?.decimals();
+        if token_program.key() != mint.owner() {
+            return Err(EscrowError::InvalidTokenProgram.into())
+        }
+
+        // Each offered mint gets its own vault, derived from the basket like an escrow vault
+        check_vault_address(vault.key(), basket.key(), mint.key(), args.vault_bumps[i])?;
+
+        let vault_bump = [args.vault_bumps[i]];
+        let vault_seeds = [Seed::from(b"vault"), Seed::from(basket.key()), Seed::from(mint.key()), Seed::from(&vault_bump)];
+        token::create_token_account(maker, vault, mint, basket.key(), &[Signer::from(&vault_seeds)])?;
+
+        TransferChecked{
+            from: maker_ata,
+            mint,
+            to: vault,
+            authority: maker,
+            amount: args.deposits[i],
+            decimals,
+            token_program: token_program.key(),
+        }.invoke()?;
+
+        // Token-2022 transfer fees are withheld in the vault, so only record what it actually received
+        basket_account.offered[i] = BasketLeg {
+            mint: *mint.key(),
+            amount: token::token_account(vault)?.amount(),
+        };
+        basket_account.vault_bumps[i] = args.vault_bumps[i];
+    }
+
+    for (i, mint) in requested_accounts.iter().enumerate() {
+        if args.amounts[i] == 0 {
+            return Err(EscrowError::InvalidAmount.into())
+        }
+
+        token::mint(mint)?;
+
+        basket_account.requested[i] = BasketLeg {
+            mint: *mint.key(),
+            amount: args.amounts[i],
+        };
+    }
+
+    Ok(())
+}
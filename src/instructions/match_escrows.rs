@@ -0,0 +1,146 @@
+use pinocchio::{account_info::AccountInfo, instruction::{Seed, Signer}, program_error::ProgramError, sysvars::{clock::Clock, Sysvar}, ProgramResult};
+use pinocchio_log::log;
+
+use super::{check_fill, close_or_resize, read_bytes};
+use crate::{error::EscrowError, state::{Config, Escrow}, token::{self, TransferChecked}};
+
+/// Instruction data of `Match`.
+pub struct MatchArgs {
+    /// Amount of mint_y paid to the maker of escrow A, out of the vault of escrow B.
+    pub fill_a: u64,
+    /// Amount of mint_x paid to the maker of escrow B, out of the vault of escrow A.
+    pub fill_b: u64,
+}
+
+impl MatchArgs {
+    pub const LEN: usize = 8 + 8;
+}
+
+impl TryFrom<&[u8]> for MatchArgs {
+    type Error = ProgramError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        if data.len() != Self::LEN {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(MatchArgs {
+            fill_a: u64::from_le_bytes(read_bytes(data, 0)?),
+            fill_b: u64::from_le_bytes(read_bytes(data, 8)?),
+        })
+    }
+}
+
+/// Settles escrow A, offering mint_x for mint_y, against escrow B, offering mint_y for
+/// mint_x. Each escrow is filled as by `Take` with what the other one releases, so the
+/// matcher needs neither token and keeps what is left over on both sides.
+pub fn process_match_instruction(accounts: &[AccountInfo], args: MatchArgs) -> ProgramResult {
+    let [
+        matcher, maker_a, maker_b, mint_x, mint_y, escrow_a, escrow_b, vault_a, vault_b, maker_a_ata_y, maker_b_ata_x, matcher_ata_x,
+        matcher_ata_y, token_program_x, token_program_y, _system_program, config, treasury_x, treasury_y,
+    ] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys)
+    };
+
+    if !matcher.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature)
+    }
+    if escrow_a.key() == escrow_b.key() {
+        return Err(EscrowError::InvalidEscrowAddress.into())
+    }
+
+    let now = Clock::get()?.unix_timestamp;
+    let escrow_a_account = Escrow::from_account_info(escrow_a)?;
+    let escrow_b_account = Escrow::from_account_info(escrow_b)?;
+    for (escrow_account, maker) in [(&*escrow_a_account, maker_a), (&*escrow_b_account, maker_b)] {
+        // Only token-for-token trades can be matched, without approvals to check
+        if escrow_account.is_multisig() || escrow_account.offers_sol() || escrow_account.requests_sol() {
+            return Err(EscrowError::WrongEscrowType.into())
+        }
+        if escrow_account.maker != *maker.key() {
+            return Err(EscrowError::InvalidEscrowAddress.into())
+        }
+    }
+
+    // Each escrow is checked as by `Take`, with no taker so private offers are refused
+    let (receive_a, filled_a) = check_fill(escrow_a_account, maker_a, escrow_a, vault_a, mint_x, mint_y, token_program_x, None, now, args.fill_a)?;
+    let (receive_b, filled_b) = check_fill(escrow_b_account, maker_b, escrow_b, vault_b, mint_y, mint_x, token_program_y, None, now, args.fill_b)?;
+
+    // The escrows cross when what each one releases covers what the other one asks for
+    if receive_a < args.fill_b || receive_b < args.fill_a {
+        return Err(EscrowError::PriceMismatch.into())
+    }
+
+    // The payments have to land in accounts of the makers
+    let maker_a_ata_y_account = token::token_account(maker_a_ata_y)?;
+    if maker_a_ata_y_account.owner() != maker_a.key() || maker_a_ata_y_account.mint() != mint_y.key() {
+        return Err(EscrowError::InvalidTokenAccount.into())
+    }
+    let maker_b_ata_x_account = token::token_account(maker_b_ata_x)?;
+    if maker_b_ata_x_account.owner() != maker_b.key() || maker_b_ata_x_account.mint() != mint_x.key() {
+        return Err(EscrowError::InvalidTokenAccount.into())
+    }
+
+    // The protocol fee is taken out of both payments, as for two takes
+    let config_account = Config::from_account_info(config)?;
+    let fee_a = config_account.fee(args.fill_a);
+    let fee_b = config_account.fee(args.fill_b);
+    for (treasury, fee, mint) in [(treasury_y, fee_a, mint_y), (treasury_x, fee_b, mint_x)] {
+        if fee > 0 {
+            let treasury_account = token::token_account(treasury)?;
+            if treasury_account.owner() != &config_account.treasury || treasury_account.mint() != mint.key() {
+                return Err(EscrowError::InvalidTokenAccount.into())
+            }
+        }
+    }
+
+    let seed_a = escrow_a_account.seed.to_le_bytes();
+    let bump_a = [escrow_a_account.bump];
+    let signer_seeds_a = [Seed::from(b"escrow"), Seed::from(maker_a.key()), Seed::from(&seed_a), Seed::from(&bump_a)];
+    let seed_b = escrow_b_account.seed.to_le_bytes();
+    let bump_b = [escrow_b_account.bump];
+    let signer_seeds_b = [Seed::from(b"escrow"), Seed::from(maker_b.key()), Seed::from(&seed_b), Seed::from(&bump_b)];
+
+    // A filled escrow releases whatever is left in its vault so it can be closed
+    let release_a = if filled_a { token::token_account(vault_a)?.amount() } else { receive_a };
+    let release_b = if filled_b { token::token_account(vault_b)?.amount() } else { receive_b };
+
+    // mint_x leaves the vault of A for maker B, the treasury and the matcher
+    let decimals_x = token::mint(mint_x)?.decimals();
+    for (to, amount) in [(maker_b_ata_x, args.fill_b - fee_b), (treasury_x, fee_b), (matcher_ata_x, release_a - args.fill_b)] {
+        if amount > 0 {
+            TransferChecked{
+                from: vault_a,
+                mint: mint_x,
+                to,
+                authority: escrow_a,
+                amount,
+                decimals: decimals_x,
+                token_program: token_program_x.key(),
+            }.invoke_signed(&[Signer::from(&signer_seeds_a)])?;
+        }
+    }
+
+    // mint_y leaves the vault of B for maker A, the treasury and the matcher
+    let decimals_y = token::mint(mint_y)?.decimals();
+    for (to, amount) in [(maker_a_ata_y, args.fill_a - fee_a), (treasury_y, fee_a), (matcher_ata_y, release_b - args.fill_a)] {
+        if amount > 0 {
+            TransferChecked{
+                from: vault_b,
+                mint: mint_y,
+                to,
+                authority: escrow_b,
+                amount,
+                decimals: decimals_y,
+                token_program: token_program_y.key(),
+            }.invoke_signed(&[Signer::from(&signer_seeds_b)])?;
+        }
+    }
+
+    log!("Match: {} and {} filled, {} and {} surplus", args.fill_a, args.fill_b, release_a - args.fill_b, release_b - args.fill_a);
+
+    close_or_resize(escrow_a_account, maker_a, escrow_a, vault_a, mint_x, token_program_x, None, &signer_seeds_a, args.fill_a, receive_a, filled_a)?;
+    close_or_resize(escrow_b_account, maker_b, escrow_b, vault_b, mint_y, token_program_y, None, &signer_seeds_b, args.fill_b, receive_b, filled_b)?;
+
+    Ok(())
+}
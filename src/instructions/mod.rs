@@ -1,17 +1,113 @@
 pub mod make;
 pub mod take;
 pub mod refund;
+pub mod update;
+pub mod deposit;
+pub mod withdraw;
+pub mod make_basket;
+pub mod take_basket;
+pub mod refund_basket;
+pub mod init_config;
+pub mod set_paused;
+pub mod set_admin;
+pub mod dispute;
+pub mod resolve;
+pub mod init_approvals;
+pub mod approve;
+pub mod claim;
+pub mod claim_vested;
+pub mod bid;
+pub mod settle;
+pub mod match_escrows;
+pub mod expire;
 
 pub use make::*;
 pub use take::*;
 pub use refund::*;
+pub use update::*;
+pub use deposit::*;
+pub use withdraw::*;
+pub use make_basket::*;
+pub use take_basket::*;
+pub use refund_basket::*;
+pub use init_config::*;
+pub use set_paused::*;
+pub use set_admin::*;
+pub use dispute::*;
+pub use resolve::*;
+pub use init_approvals::*;
+pub use approve::*;
+pub use claim::*;
+pub use claim_vested::*;
+pub use bid::*;
+pub use settle::*;
+pub use match_escrows::*;
+pub use expire::*;
 
 use pinocchio::program_error::ProgramError;
 
+/// Reads `N` bytes of instruction data at `offset`, failing on short data
+/// instead of reading past its end.
+pub fn read_bytes<const N: usize>(data: &[u8], offset: usize) -> Result<[u8; N], ProgramError> {
+    data.get(offset..offset + N)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(ProgramError::InvalidInstructionData)
+}
+
 pub enum EscrowInstructions{
     Make = 0,
     Take = 1,
     Refund = 2,
+    Update = 3,
+    Deposit = 4,
+    Withdraw = 5,
+    MakeBasket = 6,
+    TakeBasket = 7,
+    RefundBasket = 8,
+    InitConfig = 9,
+    SetPaused = 10,
+    SetAdmin = 11,
+    Dispute = 12,
+    Resolve = 13,
+    InitApprovals = 14,
+    Approve = 15,
+    Claim = 16,
+    ClaimVested = 17,
+    Bid = 18,
+    Settle = 19,
+    Match = 20,
+    Expire = 21,
+}
+
+impl EscrowInstructions {
+    /// Position of the config account for the instructions refused while the
+    /// program is paused, `None` for those always allowed.
+    pub fn config_index(&self) -> Option<usize> {
+        match self {
+            EscrowInstructions::Make => Some(8),
+            EscrowInstructions::Take => Some(12),
+            EscrowInstructions::MakeBasket => Some(3),
+            EscrowInstructions::TakeBasket => Some(4),
+            EscrowInstructions::Bid => Some(10),
+            EscrowInstructions::Match => Some(16),
+            _ => None,
+        }
+    }
+
+    /// Whether the instruction has arguments after the discriminator. Those
+    /// without are refused any trailing data.
+    pub fn has_args(&self) -> bool {
+        !matches!(
+            self,
+            EscrowInstructions::Refund
+                | EscrowInstructions::TakeBasket
+                | EscrowInstructions::RefundBasket
+                | EscrowInstructions::Dispute
+                | EscrowInstructions::ClaimVested
+                | EscrowInstructions::Settle
+                | EscrowInstructions::Expire
+        )
+    }
 }
 
 
@@ -23,6 +119,25 @@ impl TryFrom<&u8> for EscrowInstructions {
             0 => Ok(EscrowInstructions::Make),
             1 => Ok(EscrowInstructions::Take),
             2 => Ok(EscrowInstructions::Refund),
+            3 => Ok(EscrowInstructions::Update),
+            4 => Ok(EscrowInstructions::Deposit),
+            5 => Ok(EscrowInstructions::Withdraw),
+            6 => Ok(EscrowInstructions::MakeBasket),
+            7 => Ok(EscrowInstructions::TakeBasket),
+            8 => Ok(EscrowInstructions::RefundBasket),
+            9 => Ok(EscrowInstructions::InitConfig),
+            10 => Ok(EscrowInstructions::SetPaused),
+            11 => Ok(EscrowInstructions::SetAdmin),
+            12 => Ok(EscrowInstructions::Dispute),
+            13 => Ok(EscrowInstructions::Resolve),
+            14 => Ok(EscrowInstructions::InitApprovals),
+            15 => Ok(EscrowInstructions::Approve),
+            16 => Ok(EscrowInstructions::Claim),
+            17 => Ok(EscrowInstructions::ClaimVested),
+            18 => Ok(EscrowInstructions::Bid),
+            19 => Ok(EscrowInstructions::Settle),
+            20 => Ok(EscrowInstructions::Match),
+            21 => Ok(EscrowInstructions::Expire),
             _ => Err(ProgramError::InvalidInstructionData),
         }
     }
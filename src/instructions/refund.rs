@@ -1,50 +1,139 @@
-use pinocchio::{self, account_info::AccountInfo, instruction::{Seed, Signer}, program_error::ProgramError, pubkey::find_program_address, ProgramResult};
-use pinocchio_token::{instructions::{CloseAccount, Transfer}, state::TokenAccount};
+use pinocchio::{self, account_info::AccountInfo, instruction::{Seed, Signer}, program_error::ProgramError, sysvars::{clock::Clock, Sysvar}, ProgramResult};
 
-use crate::state::Escrow;
+use super::EXPIRE_TIP;
+use crate::{error::EscrowError, state::{Approvals, Escrow}, token::{self, CloseAccount, TransferChecked}};
 
 pub fn process_refund_instruction(accounts: &[AccountInfo], _data: &[u8])-> ProgramResult{
+    let [maker, _mint_x, _maker_ata_x, _vault, escrow, ..] = accounts else{
+        return Err(ProgramError::NotEnoughAccountKeys)
+    };
+
+    if !maker.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature)
+    }
+
+    // Refunds are allowed whether or not the escrow has expired, but not during a dispute
+    let escrow_account = Escrow::from_account_info(escrow)?;
+    if escrow_account.is_disputed() {
+        return Err(EscrowError::Disputed.into())
+    }
+    // Vesting grants can't be taken back once made
+    if escrow_account.is_vesting() {
+        return Err(EscrowError::WrongEscrowType.into())
+    }
+
+    // The taker of a hash time-locked escrow can claim it until the timelock
+    if escrow_account.is_htlc() && Clock::get()?.unix_timestamp < escrow_account.expiry {
+        return Err(EscrowError::TimelockActive.into())
+    }
+    // The highest bidder is owed the deposit once the auction ends
+    if escrow_account.auction(escrow).is_some_and(|auction| auction.has_bid()) {
+        return Err(EscrowError::AuctionHasBids.into())
+    }
+
+    close_to_maker(accounts, escrow_account, None)
+}
+
+/// Returns the deposit of an escrow to its maker and closes the escrow, its vault and
+/// the accounts following the refund accounts, as checked by `Refund` or `Expire`.
+/// `keeper` gets `EXPIRE_TIP` out of the escrow lamports, the maker the rest.
+pub(crate) fn close_to_maker(accounts: &[AccountInfo], escrow_account: &Escrow, keeper: Option<&AccountInfo>) -> ProgramResult {
     let [
-        maker, mint_x, maker_ata_x, vault, escrow, _token_program, _system_program
+        maker, mint_x, maker_ata_x, vault, escrow, token_program, _system_program, remaining @ ..
     ] = accounts else{
         return Err(ProgramError::NotEnoughAccountKeys)
     };
 
-    let escrow_account = Escrow::from_account_info_unchecked(escrow);
-    assert_eq!(escrow_account.mint_x, *mint_x.key());
+    if escrow_account.mint_x != *mint_x.key() {
+        return Err(EscrowError::MintMismatch.into())
+    }
 
-    let vault_account = TokenAccount::from_account_info(vault)?;
+    escrow_account.check_address(escrow.key(), maker.key())?;
 
-    let seed = [(b"escrow"), maker.key().as_slice(), &[escrow_account.bump]];
-    let seeds = &seed[..];
-    let escrow_pda = find_program_address(seeds, &crate::ID).0;
-    assert_eq!(*escrow.key(), escrow_pda);
+    let seed = escrow_account.seed.to_le_bytes();
+    let bump = [escrow_account.bump];
 
-    let bump = [escrow_account.bump.to_le()];
-    let seed = [Seed::from(b"escrow"), Seed::from(maker.key()), Seed::from(&bump)];
-    let seeds = Signer::from(&seed);
+    // The approvals of a multisig escrow follow the other accounts and are closed with it
+    let approvals = match remaining {
+        _ if !escrow_account.is_multisig() => None,
+        [approvals, ..] => {
+            Approvals::from_account_info(approvals, escrow.key())?;
+            Some(approvals)
+        }
+        [] => return Err(ProgramError::NotEnoughAccountKeys),
+    };
 
-    // Transfering mint_x from vault to maker_ata_x
-    Transfer{
-        from: vault,
-        to: maker_ata_x,
-        authority: escrow,
-        amount: vault_account.amount(),
-    }.invoke_signed(&[seeds.clone()])?;
+    // The bid vault of an auction asking for tokens follows the other accounts and is closed with it
+    let auction = escrow_account.auction(escrow).filter(|_| !escrow_account.requests_sol());
+    let bid_vault = match (auction, remaining) {
+        (None, _) => None,
+        (Some(auction), [bid_vault, ..]) => {
+            auction.check_bid_vault(bid_vault.key(), escrow.key(), &escrow_account.mint_y)?;
+            Some(bid_vault)
+        }
+        (Some(_), []) => return Err(ProgramError::NotEnoughAccountKeys),
+    };
+
+    // The vault is the token account Make created at a PDA of the escrow
+    if !escrow_account.offers_sol() {
+        escrow_account.check_vault(vault.key(), escrow.key(), mint_x.key())?;
+    }
+
+    // Lamports on offer are held by the escrow account and go back with it
+    if !escrow_account.offers_sol() {
+        if token_program.key() != mint_x.owner() {
+            return Err(EscrowError::InvalidTokenProgram.into())
+        }
 
-    // Closing Vault Account and sending lamport to maker 
-    CloseAccount{
-        account: vault,
-        destination: maker,
-        authority: escrow,
-    }.invoke_signed(&[seeds])?;
+        let vault_amount = token::token_account(vault)?.amount();
 
-    // Closing Escrow and sending lamports to maker
+        let seed = [Seed::from(b"escrow"), Seed::from(maker.key()), Seed::from(&seed), Seed::from(&bump)];
+        let seeds = Signer::from(&seed);
+
+        // Transfering mint_x from vault to maker_ata_x
+        TransferChecked{
+            from: vault,
+            mint: mint_x,
+            to: maker_ata_x,
+            authority: escrow,
+            amount: vault_amount,
+            decimals: token::mint(mint_x)?.decimals(),
+            token_program: token_program.key(),
+        }.invoke_signed(core::slice::from_ref(&seeds))?;
+
+        token::close_vault(vault, mint_x, maker, escrow, token_program.key(), &[seeds])?;
+    }
+
+    // Tokens sent to the bid vault without a bid would block its closing, it is then left open
+    if let Some(bid_vault) = bid_vault {
+        if token::token_account(bid_vault)?.amount() == 0 {
+            let seed = [Seed::from(b"escrow"), Seed::from(maker.key()), Seed::from(&seed), Seed::from(&bump)];
+
+            CloseAccount{
+                account: bid_vault,
+                destination: maker,
+                authority: escrow,
+                token_program: bid_vault.owner(),
+            }.invoke_signed(&[Signer::from(&seed)])?;
+        }
+    }
+
+    // Closing Escrow, and its approvals, and sending lamports to maker
     unsafe{
+        if let Some(keeper) = keeper {
+            *escrow.borrow_mut_lamports_unchecked() -= EXPIRE_TIP;
+            *keeper.borrow_mut_lamports_unchecked() += EXPIRE_TIP;
+        }
+
         *maker.borrow_mut_lamports_unchecked() += *escrow.borrow_lamports_unchecked();
         *escrow.borrow_mut_lamports_unchecked() = 0;
+
+        if let Some(approvals) = approvals {
+            *maker.borrow_mut_lamports_unchecked() += *approvals.borrow_lamports_unchecked();
+            *approvals.borrow_mut_lamports_unchecked() = 0;
+        }
     }
 
 
     Ok(())
-}
\ No newline at end of file
+}
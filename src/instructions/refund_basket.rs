@@ -0,0 +1,66 @@
+use pinocchio::{account_info::AccountInfo, instruction::{Seed, Signer}, program_error::ProgramError, ProgramResult};
+
+use crate::{error::EscrowError, state::Basket, token::{self, TransferChecked}};
+
+/// Accounts: maker, basket, system_program, followed by `mint, maker_ata, vault, token_program`
+/// for each offered mint.
+pub fn process_refund_basket_instruction(accounts: &[AccountInfo], _data: &[u8]) -> ProgramResult {
+    let [maker, basket, _system_program, legs @ ..] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys)
+    };
+
+    if !maker.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature)
+    }
+
+    // Refunds are allowed whether or not the basket has expired
+    let basket_account = Basket::from_account_info(basket)?;
+
+    basket_account.check_address(basket.key(), maker.key())?;
+
+    let seed = basket_account.seed.to_le_bytes();
+    let bump = [basket_account.bump];
+
+    if legs.len() != basket_account.offered_len as usize * 4 {
+        return Err(ProgramError::NotEnoughAccountKeys)
+    }
+
+    let signer_seeds = [Seed::from(b"basket"), Seed::from(maker.key()), Seed::from(&seed), Seed::from(&bump)];
+
+    for (i, (leg, leg_accounts)) in basket_account.offered().iter().zip(legs.chunks_exact(4)).enumerate() {
+        let [mint, maker_ata, vault, token_program] = leg_accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys)
+        };
+
+        if leg.mint != *mint.key() {
+            return Err(EscrowError::MintMismatch.into())
+        }
+        if token_program.key() != mint.owner() {
+            return Err(EscrowError::InvalidTokenProgram.into())
+        }
+
+        basket_account.check_vault(i, vault.key(), basket.key(), mint.key())?;
+
+        let vault_amount = token::token_account(vault)?.amount();
+
+        TransferChecked{
+            from: vault,
+            mint,
+            to: maker_ata,
+            authority: basket,
+            amount: vault_amount,
+            decimals: token::mint(mint)?.decimals(),
+            token_program: token_program.key(),
+        }.invoke_signed(&[Signer::from(&signer_seeds)])?;
+
+        token::close_vault(vault, mint, maker, basket, token_program.key(), &[Signer::from(&signer_seeds)])?;
+    }
+
+    // Closing Basket and sending lamports to maker
+    unsafe{
+        *maker.borrow_mut_lamports_unchecked() += *basket.borrow_lamports_unchecked();
+        *basket.borrow_mut_lamports_unchecked() = 0;
+    }
+
+    Ok(())
+}
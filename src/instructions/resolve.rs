@@ -0,0 +1,165 @@
+use pinocchio::{account_info::AccountInfo, instruction::{Seed, Signer}, program_error::ProgramError, ProgramResult};
+use pinocchio_log::log;
+
+use super::read_bytes;
+use crate::{error::EscrowError, state::{Approvals, Escrow}, token::{self, TransferChecked}};
+
+/// How the arbiter settles a dispute.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resolution {
+    /// The whole deposit goes to the taker.
+    Release,
+    /// The whole deposit goes back to the maker.
+    Refund,
+    /// The taker receives the given amount of the deposit and the maker the rest.
+    Split(u64),
+}
+
+/// Instruction data of `Resolve`.
+pub struct ResolveArgs {
+    pub resolution: Resolution,
+}
+
+impl ResolveArgs {
+    /// A resolution kind followed by the amount of a split, zero for the other kinds.
+    pub const LEN: usize = 1 + 8;
+}
+
+impl TryFrom<&[u8]> for ResolveArgs {
+    type Error = ProgramError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        if data.len() != Self::LEN {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let amount = u64::from_le_bytes(read_bytes(data, 1)?);
+        let resolution = match (data[0], amount) {
+            (0, 0) => Resolution::Release,
+            (1, 0) => Resolution::Refund,
+            (2, amount) => Resolution::Split(amount),
+            _ => return Err(ProgramError::InvalidInstructionData),
+        };
+
+        Ok(ResolveArgs { resolution })
+    }
+}
+
+/// Settles an open dispute, signed by the arbiter. The deposit is paid out according to
+/// the resolution and the vault and escrow are closed to the maker.
+pub fn process_resolve_instruction(accounts: &[AccountInfo], args: ResolveArgs) -> ProgramResult {
+    let [
+        arbiter, maker, taker, mint_x, maker_ata_x, taker_ata_x, vault, escrow, token_program, _system_program, remaining @ ..
+    ] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys)
+    };
+
+    if !arbiter.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature)
+    }
+
+    let escrow_account = Escrow::from_account_info(escrow)?;
+    if !escrow_account.has_arbiter() || escrow_account.arbiter != *arbiter.key() {
+        return Err(EscrowError::InvalidArbiter.into())
+    }
+    if !escrow_account.is_disputed() {
+        return Err(EscrowError::NotDisputed.into())
+    }
+    if escrow_account.maker != *maker.key() || escrow_account.taker != *taker.key() {
+        return Err(EscrowError::NotAParty.into())
+    }
+    if escrow_account.mint_x != *mint_x.key() {
+        return Err(EscrowError::MintMismatch.into())
+    }
+
+    escrow_account.check_address(escrow.key(), maker.key())?;
+
+    let seed = escrow_account.seed.to_le_bytes();
+    let bump = [escrow_account.bump];
+
+    // The approvals of a multisig escrow follow the other accounts and are closed with it
+    let approvals = match remaining {
+        _ if !escrow_account.is_multisig() => None,
+        [approvals, ..] => {
+            Approvals::from_account_info(approvals, escrow.key())?;
+            Some(approvals)
+        }
+        [] => return Err(ProgramError::NotEnoughAccountKeys),
+    };
+
+    let offers_sol = escrow_account.offers_sol();
+    if !offers_sol {
+        escrow_account.check_vault(vault.key(), escrow.key(), mint_x.key())?;
+
+        if token_program.key() != mint_x.owner() {
+            return Err(EscrowError::InvalidTokenProgram.into())
+        }
+    }
+
+    // Everything the vault holds is paid out, like on a refund
+    let total = if offers_sol {
+        escrow_account.deposit
+    } else {
+        token::token_account(vault)?.amount()
+    };
+    let to_taker = match args.resolution {
+        Resolution::Release => total,
+        Resolution::Refund => 0,
+        Resolution::Split(amount) => {
+            if amount > total {
+                return Err(EscrowError::InvalidAmount.into())
+            }
+            amount
+        }
+    };
+    let to_maker = total - to_taker;
+
+    log!("Resolve: {} to taker, {} to maker", to_taker, to_maker);
+
+    if offers_sol {
+        // The maker's share goes back with the escrow account below
+        unsafe {
+            *escrow.borrow_mut_lamports_unchecked() -= to_taker;
+            *taker.borrow_mut_lamports_unchecked() += to_taker;
+        }
+    } else {
+        let signer_seeds = [Seed::from(b"escrow"), Seed::from(maker.key()), Seed::from(&seed), Seed::from(&bump)];
+        let decimals = token::mint(mint_x)?.decimals();
+
+        for (owner, destination, amount) in [(taker, taker_ata_x, to_taker), (maker, maker_ata_x, to_maker)] {
+            if amount == 0 {
+                continue
+            }
+
+            let destination_account = token::token_account(destination)?;
+            if destination_account.owner() != owner.key() || destination_account.mint() != mint_x.key() {
+                return Err(EscrowError::InvalidTokenAccount.into())
+            }
+
+            TransferChecked{
+                from: vault,
+                mint: mint_x,
+                to: destination,
+                authority: escrow,
+                amount,
+                decimals,
+                token_program: token_program.key(),
+            }.invoke_signed(&[Signer::from(&signer_seeds)])?;
+        }
+
+        token::close_vault(vault, mint_x, maker, escrow, token_program.key(), &[Signer::from(&signer_seeds)])?;
+    }
+
+    // Closing Escrow, and its approvals, and sending lamports to maker
+    unsafe{
+        *maker.borrow_mut_lamports_unchecked() += *escrow.borrow_lamports_unchecked();
+        *escrow.borrow_mut_lamports_unchecked() = 0;
+
+        if let Some(approvals) = approvals {
+            *maker.borrow_mut_lamports_unchecked() += *approvals.borrow_lamports_unchecked();
+            *approvals.borrow_mut_lamports_unchecked() = 0;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey, ProgramResult};
+
+use super::read_bytes;
+use crate::{error::EscrowError, state::Config};
+
+/// Instruction data of `SetAdmin`.
+pub struct SetAdminArgs {
+    /// New admin of the config.
+    pub admin: Pubkey,
+}
+
+impl SetAdminArgs {
+    pub const LEN: usize = 32;
+}
+
+impl TryFrom<&[u8]> for SetAdminArgs {
+    type Error = ProgramError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        if data.len() != Self::LEN {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(SetAdminArgs {
+            admin: read_bytes(data, 0)?,
+        })
+    }
+}
+
+/// Transfers the config to a new admin. The current admin loses every right over
+/// it, so the new key has to be one its owner can sign with.
+pub fn process_set_admin_instruction(accounts: &[AccountInfo], args: SetAdminArgs) -> ProgramResult {
+    let [admin, config] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys)
+    };
+
+    if !admin.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature)
+    }
+
+    let config_account = Config::from_account_info(config)?;
+    if config_account.admin != *admin.key() {
+        return Err(EscrowError::UnauthorizedAdmin.into())
+    }
+
+    config_account.admin = args.admin;
+
+    Ok(())
+}
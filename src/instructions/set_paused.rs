@@ -0,0 +1,47 @@
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, ProgramResult};
+
+use crate::{error::EscrowError, state::Config};
+
+/// Instruction data of `SetPaused`.
+pub struct SetPausedArgs {
+    /// Whether makes and takes are refused.
+    pub paused: bool,
+}
+
+impl SetPausedArgs {
+    pub const LEN: usize = 1;
+}
+
+impl TryFrom<&[u8]> for SetPausedArgs {
+    type Error = ProgramError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        if data.len() != Self::LEN || data[0] > 1 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(SetPausedArgs {
+            paused: data[0] == 1,
+        })
+    }
+}
+
+/// Pauses or resumes makes and takes. Refunds stay allowed while paused.
+pub fn process_set_paused_instruction(accounts: &[AccountInfo], args: SetPausedArgs) -> ProgramResult {
+    let [admin, config] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys)
+    };
+
+    if !admin.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature)
+    }
+
+    let config_account = Config::from_account_info(config)?;
+    if config_account.admin != *admin.key() {
+        return Err(EscrowError::UnauthorizedAdmin.into())
+    }
+
+    config_account.paused = args.paused as u8;
+
+    Ok(())
+}
@@ -0,0 +1,148 @@
+use pinocchio::{account_info::AccountInfo, instruction::{Seed, Signer}, program_error::ProgramError, sysvars::{clock::Clock, Sysvar}, ProgramResult};
+use pinocchio_log::log;
+
+use crate::{error::EscrowError, state::{Config, Escrow}, token::{self, TransferChecked}};
+
+/// Closes an auction once it ended, sending the deposit to the highest bidder and the
+/// highest bid, less the protocol fee, to the maker. Anyone can crank it.
+pub fn process_settle_instruction(accounts: &[AccountInfo], _data: &[u8]) -> ProgramResult {
+    let [
+        maker, winner, mint_x, mint_y, winner_ata_x, maker_ata_y, vault, bid_vault, escrow, token_program_x, token_program_y, _system_program,
+        config, treasury,
+    ] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys)
+    };
+
+    let escrow_account = Escrow::from_account_info(escrow)?;
+    let Some(auction) = escrow_account.auction(escrow) else {
+        return Err(EscrowError::WrongEscrowType.into())
+    };
+    if escrow_account.maker != *maker.key() {
+        return Err(EscrowError::InvalidEscrowAddress.into())
+    }
+    if escrow_account.mint_x != *mint_x.key() || escrow_account.mint_y != *mint_y.key() {
+        return Err(EscrowError::MintMismatch.into())
+    }
+
+    escrow_account.check_address(escrow.key(), maker.key())?;
+
+    let seed = escrow_account.seed.to_le_bytes();
+    let bump = [escrow_account.bump];
+
+    if Clock::get()?.unix_timestamp < escrow_account.expiry {
+        return Err(EscrowError::AuctionActive.into())
+    }
+    if !auction.has_bid() {
+        return Err(EscrowError::NoBids.into())
+    }
+    if auction.bidder != *winner.key() {
+        return Err(EscrowError::InvalidRecipient.into())
+    }
+
+    let signer_seeds = [Seed::from(b"escrow"), Seed::from(maker.key()), Seed::from(&seed), Seed::from(&bump)];
+
+    // The protocol fee is taken out of the highest bid, the maker receives the rest
+    let config_account = Config::from_account_info(config)?;
+    let fee = config_account.fee(auction.bid);
+    let proceeds = auction.bid - fee;
+
+    if escrow_account.requests_sol() {
+        if fee > 0 && treasury.key() != &config_account.treasury {
+            return Err(EscrowError::InvalidTokenAccount.into())
+        }
+
+        unsafe {
+            *escrow.borrow_mut_lamports_unchecked() -= auction.bid;
+            *maker.borrow_mut_lamports_unchecked() += proceeds;
+            *treasury.borrow_mut_lamports_unchecked() += fee;
+        }
+    } else {
+        auction.check_bid_vault(bid_vault.key(), escrow.key(), mint_y.key())?;
+        if token_program_y.key() != mint_y.owner() {
+            return Err(EscrowError::InvalidTokenProgram.into())
+        }
+
+        // Anyone can settle, so the proceeds have to go to an account of the maker
+        let maker_ata_y_account = token::token_account(maker_ata_y)?;
+        if maker_ata_y_account.owner() != maker.key() || maker_ata_y_account.mint() != mint_y.key() {
+            return Err(EscrowError::InvalidTokenAccount.into())
+        }
+
+        let decimals = token::mint(mint_y)?.decimals();
+
+        if fee > 0 {
+            let treasury_account = token::token_account(treasury)?;
+            if treasury_account.owner() != &config_account.treasury || treasury_account.mint() != mint_y.key() {
+                return Err(EscrowError::InvalidTokenAccount.into())
+            }
+
+            TransferChecked{
+                from: bid_vault,
+                mint: mint_y,
+                to: treasury,
+                authority: escrow,
+                amount: fee,
+                decimals,
+                token_program: token_program_y.key(),
+            }.invoke_signed(&[Signer::from(&signer_seeds)])?;
+        }
+
+        // The maker also gets anything sent to the bid vault on top of the bids
+        let bid_vault_amount = token::token_account(bid_vault)?.amount();
+
+        TransferChecked{
+            from: bid_vault,
+            mint: mint_y,
+            to: maker_ata_y,
+            authority: escrow,
+            amount: bid_vault_amount,
+            decimals,
+            token_program: token_program_y.key(),
+        }.invoke_signed(&[Signer::from(&signer_seeds)])?;
+
+        token::close_vault(bid_vault, mint_y, maker, escrow, token_program_y.key(), &[Signer::from(&signer_seeds)])?;
+    }
+
+    if escrow_account.offers_sol() {
+        let deposit = escrow_account.deposit;
+
+        unsafe {
+            *escrow.borrow_mut_lamports_unchecked() -= deposit;
+            *winner.borrow_mut_lamports_unchecked() += deposit;
+        }
+    } else {
+        escrow_account.check_vault(vault.key(), escrow.key(), mint_x.key())?;
+        if token_program_x.key() != mint_x.owner() {
+            return Err(EscrowError::InvalidTokenProgram.into())
+        }
+
+        let winner_ata_x_account = token::token_account(winner_ata_x)?;
+        if winner_ata_x_account.owner() != winner.key() || winner_ata_x_account.mint() != mint_x.key() {
+            return Err(EscrowError::InvalidTokenAccount.into())
+        }
+
+        let vault_amount = token::token_account(vault)?.amount();
+
+        TransferChecked{
+            from: vault,
+            mint: mint_x,
+            to: winner_ata_x,
+            authority: escrow,
+            amount: vault_amount,
+            decimals: token::mint(mint_x)?.decimals(),
+            token_program: token_program_x.key(),
+        }.invoke_signed(&[Signer::from(&signer_seeds)])?;
+
+        token::close_vault(vault, mint_x, maker, escrow, token_program_x.key(), &[Signer::from(&signer_seeds)])?;
+    }
+
+    log!("Settle: {} to maker, {} protocol fee", proceeds, fee);
+
+    // Closing Escrow and sending lamports to maker
+    unsafe{
+        *maker.borrow_mut_lamports_unchecked() += *escrow.borrow_lamports_unchecked();
+        *escrow.borrow_mut_lamports_unchecked() = 0;
+    }
+
+    Ok(())
+}
@@ -1,55 +1,324 @@
-use pinocchio::{self, account_info::AccountInfo, instruction::{Seed, Signer}, program_error::ProgramError, pubkey::find_program_address, ProgramResult};
-use pinocchio_token::{instructions::{CloseAccount, Transfer}, state::TokenAccount};
+use pinocchio::{self, account_info::AccountInfo, instruction::{Seed, Signer}, program_error::ProgramError, pubkey::Pubkey, sysvars::{clock::Clock, Sysvar}, ProgramResult};
+use pinocchio_log::log;
 
-use crate::state::Escrow;
+use super::read_bytes;
+use crate::{error::EscrowError, state::{bps_of, Approvals, Config, Escrow}, token::{self, TransferChecked}};
 
-pub fn process_take_instruction(accounts: &[AccountInfo], _data: &[u8]) -> ProgramResult{
+/// Instruction data of `Take`.
+pub struct TakeArgs {
+    /// Amount of mint_y the taker pays.
+    pub fill: u64,
+    /// Version of the escrow terms the taker agreed to.
+    pub version: u64,
+    /// Share of the payment granted to the referrer, in basis points.
+    pub referrer_bps: u16,
+}
+
+impl TakeArgs {
+    pub const LEN: usize = 8 + 8 + 2;
+}
+
+impl TryFrom<&[u8]> for TakeArgs {
+    type Error = ProgramError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        if data.len() != Self::LEN {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(TakeArgs {
+            fill: u64::from_le_bytes(read_bytes(data, 0)?),
+            version: u64::from_le_bytes(read_bytes(data, 8)?),
+            referrer_bps: u16::from_le_bytes(read_bytes(data, 16)?),
+        })
+    }
+}
+
+pub fn process_take_instruction(accounts: &[AccountInfo], args: TakeArgs) -> ProgramResult{
     let [
-        taker, maker, mint_x, mint_y, taker_ata_x, taker_ata_y, maker_ata_y, vault, escrow, _token_program, _system_program
+        taker, maker, mint_x, mint_y, taker_ata_x, taker_ata_y, maker_ata_y, vault, escrow, token_program_x, token_program_y, _system_program,
+        config, treasury, remaining @ ..
     ] = accounts else{
         return Err(ProgramError::NotEnoughAccountKeys)
     };
 
-    let escrow_account = Escrow::from_account_info_unchecked(escrow);
-    assert_eq!(escrow_account.mint_x, *mint_x.key());
-    assert_eq!(escrow_account.mint_y, *mint_y.key());
-
-    let vault_account = TokenAccount::from_account_info(vault)?;
-
-    let seed = [(b"escrow"), maker.key().as_slice(), &[escrow_account.bump]];
-    let seeds = &seed[..];
-    let escrow_pda = find_program_address(seeds, &crate::ID).0;
-    assert_eq!(*escrow.key(), escrow_pda);
-
-    Transfer{
-        from: taker_ata_y,
-        to: maker_ata_y,
-        authority: taker,
-        amount: escrow_account.amount,
-    }.invoke()?;
-
-    let bump = [escrow_account.bump.to_le()];
-    let seed = [Seed::from(b"escrow"), Seed::from(maker.key()), Seed::from(&bump)];
-    let seeds = Signer::from(&seed);
-
-    Transfer{
-        from: vault,
-        to: taker_ata_x,
-        authority: escrow,
-        amount: vault_account.amount(),
-    }.invoke_signed(&[seeds.clone()])?;
-
-    CloseAccount{
-        account: vault,
-        destination: maker,
-        authority: escrow,
-    }.invoke_signed(&[seeds])?;
+    if !taker.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature)
+    }
+
+    let now = Clock::get()?.unix_timestamp;
+    let fill = args.fill;
+    let escrow_account = Escrow::from_account_info(escrow)?;
+    let (receive, filled) = check_fill(escrow_account, maker, escrow, vault, mint_x, mint_y, token_program_x, Some(taker.key()), now, fill)?;
+
+    if !escrow_account.requests_sol() {
+        if token_program_y.key() != mint_y.owner() {
+            return Err(EscrowError::InvalidTokenProgram.into())
+        }
+
+        // The payment has to land in a mint_y account of the maker
+        let maker_ata_y_account = token::token_account(maker_ata_y)?;
+        if maker_ata_y_account.owner() != maker.key() || maker_ata_y_account.mint() != mint_y.key() {
+            return Err(EscrowError::InvalidTokenAccount.into())
+        }
+    }
+
+    // The maker may have updated the terms since the taker built the transaction
+    if args.version != escrow_account.version {
+        return Err(EscrowError::TermsChanged.into())
+    }
+
+    // Multisig escrows take their approvals first among the trailing accounts
+    let (approvals, remaining) = if escrow_account.is_multisig() {
+        let [approvals, remaining @ ..] = remaining else {
+            return Err(ProgramError::NotEnoughAccountKeys)
+        };
+        if !Approvals::from_account_info(approvals, escrow.key())?.is_approved(escrow_account.version) {
+            return Err(EscrowError::ApprovalsPending.into())
+        }
+        (Some(approvals), remaining)
+    } else {
+        (None, remaining)
+    };
+
+    // The protocol and referrer fees are taken out of the payment, the maker receives the rest
+    let config_account = Config::from_account_info(config)?;
+    if args.referrer_bps > config_account.max_referrer_bps {
+        return Err(EscrowError::InvalidReferrerFee.into())
+    }
+    let fee = config_account.fee(fill);
+    let referrer_fee = bps_of(fill, args.referrer_bps);
+    let payment = fill
+        .checked_sub(fee)
+        .and_then(|payment| payment.checked_sub(referrer_fee))
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+
+    // The referrer account is only needed when it gets a cut
+    let referrer = match remaining {
+        _ if referrer_fee == 0 => None,
+        [referrer, ..] => Some(referrer),
+        [] => return Err(ProgramError::NotEnoughAccountKeys),
+    };
+
+    if escrow_account.requests_sol() {
+        pinocchio_system::instructions::Transfer{
+            from: taker,
+            to: maker,
+            lamports: payment,
+        }.invoke()?;
+
+        if fee > 0 {
+            // Lamport fees go straight to the treasury wallet
+            if treasury.key() != &config_account.treasury {
+                return Err(EscrowError::InvalidTokenAccount.into())
+            }
+
+            pinocchio_system::instructions::Transfer{
+                from: taker,
+                to: treasury,
+                lamports: fee,
+            }.invoke()?;
+        }
+
+        if let Some(referrer) = referrer {
+            pinocchio_system::instructions::Transfer{
+                from: taker,
+                to: referrer,
+                lamports: referrer_fee,
+            }.invoke()?;
+        }
+    } else {
+        let decimals = token::mint(mint_y)?.decimals();
+
+        TransferChecked{
+            from: taker_ata_y,
+            mint: mint_y,
+            to: maker_ata_y,
+            authority: taker,
+            amount: payment,
+            decimals,
+            token_program: token_program_y.key(),
+        }.invoke()?;
+
+        if fee > 0 {
+            let treasury_account = token::token_account(treasury)?;
+            if treasury_account.owner() != &config_account.treasury || treasury_account.mint() != mint_y.key() {
+                return Err(EscrowError::InvalidTokenAccount.into())
+            }
+
+            TransferChecked{
+                from: taker_ata_y,
+                mint: mint_y,
+                to: treasury,
+                authority: taker,
+                amount: fee,
+                decimals,
+                token_program: token_program_y.key(),
+            }.invoke()?;
+        }
+
+        if let Some(referrer) = referrer {
+            if token::token_account(referrer)?.mint() != mint_y.key() {
+                return Err(EscrowError::InvalidTokenAccount.into())
+            }
+
+            TransferChecked{
+                from: taker_ata_y,
+                mint: mint_y,
+                to: referrer,
+                authority: taker,
+                amount: referrer_fee,
+                decimals,
+                token_program: token_program_y.key(),
+            }.invoke()?;
+        }
+    }
+
+    log!("Take: {} to maker, {} protocol fee, {} referrer fee", payment, fee, referrer_fee);
+
+    let seed = escrow_account.seed.to_le_bytes();
+    let bump = [escrow_account.bump];
+    let signer_seeds = [Seed::from(b"escrow"), Seed::from(maker.key()), Seed::from(&seed), Seed::from(&bump)];
 
+    if escrow_account.offers_sol() {
+        // Lamports on offer are paid out of the escrow account directly
+        unsafe {
+            *escrow.borrow_mut_lamports_unchecked() -= receive;
+            *taker.borrow_mut_lamports_unchecked() += receive;
+        }
+    } else {
+        // Last fill drains whatever is left in the vault so it can be closed
+        let amount = if filled { token::token_account(vault)?.amount() } else { receive };
+
+        TransferChecked{
+            from: vault,
+            mint: mint_x,
+            to: taker_ata_x,
+            authority: escrow,
+            amount,
+            decimals: token::mint(mint_x)?.decimals(),
+            token_program: token_program_x.key(),
+        }.invoke_signed(&[Signer::from(&signer_seeds)])?;
+    }
+
+    close_or_resize(escrow_account, maker, escrow, vault, mint_x, token_program_x, approvals, &signer_seeds, fill, receive, filled)
+}
+
+/// Checks that an escrow can be filled with `fill` of mint_y at `now`, by `taker` or, for
+/// `None`, by anyone as long as the offer is public. Returns how much of the deposit the
+/// fill releases and whether it fills the escrow whole. Shared by `Take` and `Match`.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn check_fill(
+    escrow_account: &Escrow,
+    maker: &AccountInfo,
+    escrow: &AccountInfo,
+    vault: &AccountInfo,
+    mint_x: &AccountInfo,
+    mint_y: &AccountInfo,
+    token_program_x: &AccountInfo,
+    taker: Option<&Pubkey>,
+    now: i64,
+    fill: u64,
+) -> Result<(u64, bool), ProgramError> {
+    if escrow_account.is_disputed() {
+        return Err(EscrowError::Disputed.into());
+    }
+    if !escrow_account.is_trade() {
+        return Err(EscrowError::WrongEscrowType.into());
+    }
+    if escrow_account.mint_x != *mint_x.key() || escrow_account.mint_y != *mint_y.key() {
+        return Err(EscrowError::MintMismatch.into());
+    }
+
+    escrow_account.check_address(escrow.key(), maker.key())?;
+
+    // The vault is the token account Make created at a PDA of the escrow, moved with
+    // the program owning its mint
+    if !escrow_account.offers_sol() {
+        if token_program_x.key() != mint_x.owner() {
+            return Err(EscrowError::InvalidTokenProgram.into());
+        }
+        escrow_account.check_vault(vault.key(), escrow.key(), mint_x.key())?;
+    }
+
+    // Private offers can only be filled by the taker chosen by the maker
+    if escrow_account.taker != Pubkey::default() && taker != Some(&escrow_account.taker) {
+        return Err(EscrowError::UnauthorizedTaker.into());
+    }
+
+    // Expired offers can no longer be taken, only refunded
+    if escrow_account.expiry != 0 && now >= escrow_account.expiry {
+        return Err(EscrowError::EscrowExpired.into());
+    }
+
+    // Dutch auctions ask for less as time passes, other escrows for their fixed amount
+    let amount = escrow_account.dutch(escrow).map_or(escrow_account.amount, |dutch| dutch.price(escrow_account.amount, now));
+    if fill == 0 || fill > amount {
+        return Err(EscrowError::InvalidFillAmount.into());
+    }
+
+    // NFTs can't be split, so NFT escrows are only filled whole
+    if (escrow_account.offers_nft() || escrow_account.requests_nft()) && fill != amount {
+        return Err(EscrowError::InvalidFillAmount.into());
+    }
+
+    // The taker receives the same share of the deposit as the share of the
+    // remaining amount they pay, rounded down in favour of the maker
+    let filled = fill == amount;
+    let receive = if filled {
+        escrow_account.deposit
+    } else {
+        (escrow_account.deposit as u128 * fill as u128 / amount as u128) as u64
+    };
+    if receive == 0 {
+        return Err(EscrowError::FillTooSmall.into());
+    }
+
+    Ok((receive, filled))
+}
+
+/// Updates the terms of a partly filled escrow, or closes a filled one with its vault,
+/// emptied by the caller, and its approvals to the maker. Shared by `Take` and `Match`.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn close_or_resize(
+    escrow_account: &mut Escrow,
+    maker: &AccountInfo,
+    escrow: &AccountInfo,
+    vault: &AccountInfo,
+    mint_x: &AccountInfo,
+    token_program_x: &AccountInfo,
+    approvals: Option<&AccountInfo>,
+    signer_seeds: &[Seed],
+    fill: u64,
+    receive: u64,
+    filled: bool,
+) -> ProgramResult {
+    if !filled {
+        if let Some(dutch) = escrow_account.dutch(escrow) {
+            // The rest of the deposit keeps decaying along the same curve
+            escrow_account.resize(Some(dutch), escrow_account.deposit - receive)?;
+        } else {
+            escrow_account.amount -= fill;
+            escrow_account.deposit -= receive;
+        }
+
+        return Ok(());
+    }
+
+    if !escrow_account.offers_sol() {
+        token::close_vault(vault, mint_x, maker, escrow, token_program_x.key(), &[Signer::from(signer_seeds)])?;
+    }
+
+    // Closing Escrow, and its approvals, and sending the remaining lamports to maker
     unsafe{
         *maker.borrow_mut_lamports_unchecked() += *escrow.borrow_lamports_unchecked();
         *escrow.borrow_mut_lamports_unchecked() = 0;
-    }
 
+        if let Some(approvals) = approvals {
+            *maker.borrow_mut_lamports_unchecked() += *approvals.borrow_lamports_unchecked();
+            *approvals.borrow_mut_lamports_unchecked() = 0;
+        }
+    }
 
     Ok(())
-}
\ No newline at end of file
+}
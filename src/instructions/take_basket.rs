@@ -0,0 +1,131 @@
+use pinocchio::{account_info::AccountInfo, instruction::{Seed, Signer}, program_error::ProgramError, pubkey::Pubkey, sysvars::{clock::Clock, Sysvar}, ProgramResult};
+
+use crate::{error::EscrowError, state::{Basket, Config}, token::{self, TransferChecked}};
+
+/// Accounts: taker, maker, basket, system_program, config, followed by `mint, taker_ata, maker_ata, treasury_ata, token_program`
+/// for each requested mint and `mint, vault, taker_ata, token_program` for each offered mint.
+/// The treasury token account of a requested mint is only read while the protocol fee is non-zero.
+pub fn process_take_basket_instruction(accounts: &[AccountInfo], _data: &[u8]) -> ProgramResult {
+    let [taker, maker, basket, _system_program, config, legs @ ..] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys)
+    };
+
+    if !taker.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature)
+    }
+
+    let basket_account = Basket::from_account_info(basket)?;
+
+    basket_account.check_address(basket.key(), maker.key())?;
+
+    let seed = basket_account.seed.to_le_bytes();
+    let bump = [basket_account.bump];
+
+    // Private baskets can only be taken by the taker chosen by the maker
+    if basket_account.taker != Pubkey::default() && basket_account.taker != *taker.key() {
+        return Err(EscrowError::UnauthorizedTaker.into())
+    }
+
+    // Expired baskets can no longer be taken, only refunded
+    if basket_account.expiry != 0 && Clock::get()?.unix_timestamp >= basket_account.expiry {
+        return Err(EscrowError::EscrowExpired.into())
+    }
+
+    let requested_len = basket_account.requested_len as usize;
+    let offered_len = basket_account.offered_len as usize;
+    if legs.len() != requested_len * 5 + offered_len * 4 {
+        return Err(ProgramError::NotEnoughAccountKeys)
+    }
+    let (requested_accounts, offered_accounts) = legs.split_at(requested_len * 5);
+
+    let config_account = Config::from_account_info(config)?;
+
+    // The taker pays every requested mint to the maker, less the protocol fee as in Take...
+    for (leg, leg_accounts) in basket_account.requested().iter().zip(requested_accounts.chunks_exact(5)) {
+        let [mint, taker_ata, maker_ata, treasury_ata, token_program] = leg_accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys)
+        };
+
+        if leg.mint != *mint.key() {
+            return Err(EscrowError::MintMismatch.into())
+        }
+        if token_program.key() != mint.owner() {
+            return Err(EscrowError::InvalidTokenProgram.into())
+        }
+
+        let maker_ata_account = token::token_account(maker_ata)?;
+        if maker_ata_account.owner() != maker.key() || maker_ata_account.mint() != mint.key() {
+            return Err(EscrowError::InvalidTokenAccount.into())
+        }
+
+        let fee = config_account.fee(leg.amount);
+        let decimals = token::mint(mint)?.decimals();
+
+        TransferChecked{
+            from: taker_ata,
+            mint,
+            to: maker_ata,
+            authority: taker,
+            amount: leg.amount - fee,
+            decimals,
+            token_program: token_program.key(),
+        }.invoke()?;
+
+        if fee > 0 {
+            let treasury_account = token::token_account(treasury_ata)?;
+            if treasury_account.owner() != &config_account.treasury || treasury_account.mint() != mint.key() {
+                return Err(EscrowError::InvalidTokenAccount.into())
+            }
+
+            TransferChecked{
+                from: taker_ata,
+                mint,
+                to: treasury_ata,
+                authority: taker,
+                amount: fee,
+                decimals,
+                token_program: token_program.key(),
+            }.invoke()?;
+        }
+    }
+
+    let signer_seeds = [Seed::from(b"basket"), Seed::from(maker.key()), Seed::from(&seed), Seed::from(&bump)];
+
+    // ...and receives every offered mint, closing its vault
+    for (i, (leg, leg_accounts)) in basket_account.offered().iter().zip(offered_accounts.chunks_exact(4)).enumerate() {
+        let [mint, vault, taker_ata, token_program] = leg_accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys)
+        };
+
+        if leg.mint != *mint.key() {
+            return Err(EscrowError::MintMismatch.into())
+        }
+        if token_program.key() != mint.owner() {
+            return Err(EscrowError::InvalidTokenProgram.into())
+        }
+
+        basket_account.check_vault(i, vault.key(), basket.key(), mint.key())?;
+
+        let vault_amount = token::token_account(vault)?.amount();
+
+        TransferChecked{
+            from: vault,
+            mint,
+            to: taker_ata,
+            authority: basket,
+            amount: vault_amount,
+            decimals: token::mint(mint)?.decimals(),
+            token_program: token_program.key(),
+        }.invoke_signed(&[Signer::from(&signer_seeds)])?;
+
+        token::close_vault(vault, mint, maker, basket, token_program.key(), &[Signer::from(&signer_seeds)])?;
+    }
+
+    // Closing Basket and sending the remaining lamports to maker
+    unsafe{
+        *maker.borrow_mut_lamports_unchecked() += *basket.borrow_lamports_unchecked();
+        *basket.borrow_mut_lamports_unchecked() = 0;
+    }
+
+    Ok(())
+}
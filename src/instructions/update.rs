@@ -0,0 +1,81 @@
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey, sysvars::{clock::Clock, Sysvar}, ProgramResult};
+
+use super::read_bytes;
+use crate::{error::EscrowError, state::Escrow};
+
+/// Instruction data of `Update`, the new terms of the escrow.
+pub struct UpdateArgs {
+    /// Amount of mint_y requested for the remaining deposit.
+    pub amount: u64,
+    /// Unix timestamp after which the offer can't be taken, `0` for none.
+    pub expiry: i64,
+    /// Only taker allowed to fill the offer, the default public key for anyone.
+    pub taker: Pubkey,
+}
+
+impl UpdateArgs {
+    pub const LEN: usize = 8 + 8 + 32;
+}
+
+impl TryFrom<&[u8]> for UpdateArgs {
+    type Error = ProgramError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        if data.len() != Self::LEN {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(UpdateArgs {
+            amount: u64::from_le_bytes(read_bytes(data, 0)?),
+            expiry: i64::from_le_bytes(read_bytes(data, 8)?),
+            taker: read_bytes(data, 16)?,
+        })
+    }
+}
+
+pub fn process_update_instruction(accounts: &[AccountInfo], args: UpdateArgs) -> ProgramResult {
+    let [maker, escrow] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys)
+    };
+
+    if !maker.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature)
+    }
+
+    let escrow_account = Escrow::from_account_info(escrow)?;
+    if escrow_account.is_disputed() {
+        return Err(EscrowError::Disputed.into())
+    }
+    if !escrow_account.is_trade() {
+        return Err(EscrowError::WrongEscrowType.into())
+    }
+
+    escrow_account.check_address(escrow.key(), maker.key())?;
+
+    if args.amount == 0 {
+        return Err(EscrowError::InvalidAmount.into())
+    }
+    if escrow_account.requests_nft() && args.amount != 1 {
+        return Err(EscrowError::InvalidNftAmount.into())
+    }
+    // The start price of a Dutch auction stays above its floor
+    if escrow_account.dutch(escrow).is_some_and(|dutch| args.amount <= dutch.floor) {
+        return Err(EscrowError::InvalidAmount.into())
+    }
+    // An arbitrated deal keeps a designated taker other than the arbiter
+    if escrow_account.has_arbiter() && (args.taker == Pubkey::default() || args.taker == escrow_account.arbiter) {
+        return Err(EscrowError::InvalidArbiter.into())
+    }
+    if args.expiry != 0 && args.expiry <= Clock::get()?.unix_timestamp {
+        return Err(EscrowError::InvalidExpiry.into())
+    }
+
+    escrow_account.amount = args.amount;
+    escrow_account.expiry = args.expiry;
+    escrow_account.taker = args.taker;
+
+    // Takes built against the previous terms are rejected
+    escrow_account.version += 1;
+
+    Ok(())
+}
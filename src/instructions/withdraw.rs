@@ -0,0 +1,95 @@
+use pinocchio::{account_info::AccountInfo, instruction::{Seed, Signer}, program_error::ProgramError, ProgramResult};
+
+use super::read_bytes;
+use crate::{error::EscrowError, state::Escrow, token::{self, TransferChecked}};
+
+/// Instruction data of `Withdraw`.
+pub struct WithdrawArgs {
+    /// Amount of mint_x taken out of the deposit.
+    pub amount: u64,
+}
+
+impl WithdrawArgs {
+    pub const LEN: usize = 8;
+}
+
+impl TryFrom<&[u8]> for WithdrawArgs {
+    type Error = ProgramError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        if data.len() != Self::LEN {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(WithdrawArgs {
+            amount: u64::from_le_bytes(read_bytes(data, 0)?),
+        })
+    }
+}
+
+pub fn process_withdraw_instruction(accounts: &[AccountInfo], args: WithdrawArgs) -> ProgramResult {
+    let [maker, mint_x, maker_ata_x, vault, escrow, token_program, _system_program] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys)
+    };
+
+    if !maker.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature)
+    }
+
+    let escrow_account = Escrow::from_account_info(escrow)?;
+    if escrow_account.is_disputed() {
+        return Err(EscrowError::Disputed.into())
+    }
+    if !escrow_account.is_trade() {
+        return Err(EscrowError::WrongEscrowType.into())
+    }
+    if escrow_account.mint_x != *mint_x.key() {
+        return Err(EscrowError::MintMismatch.into())
+    }
+
+    escrow_account.check_address(escrow.key(), maker.key())?;
+
+    let seed = escrow_account.seed.to_le_bytes();
+    let bump = [escrow_account.bump];
+
+    if args.amount == 0 {
+        return Err(EscrowError::InvalidAmount.into())
+    }
+    // Taking out everything is a refund, which also closes the accounts
+    if args.amount >= escrow_account.deposit {
+        return Err(EscrowError::InsufficientDeposit.into())
+    }
+
+    if escrow_account.offers_sol() {
+        // Lamports on offer are paid out of the escrow account directly
+        unsafe {
+            *escrow.borrow_mut_lamports_unchecked() -= args.amount;
+            *maker.borrow_mut_lamports_unchecked() += args.amount;
+        }
+    } else {
+        escrow_account.check_vault(vault.key(), escrow.key(), mint_x.key())?;
+
+        if token_program.key() != mint_x.owner() {
+            return Err(EscrowError::InvalidTokenProgram.into())
+        }
+
+        let seed = [Seed::from(b"escrow"), Seed::from(maker.key()), Seed::from(&seed), Seed::from(&bump)];
+
+        TransferChecked{
+            from: vault,
+            mint: mint_x,
+            to: maker_ata_x,
+            authority: escrow,
+            amount: args.amount,
+            decimals: token::mint(mint_x)?.decimals(),
+            token_program: token_program.key(),
+        }.invoke_signed(&[Signer::from(&seed)])?;
+    }
+
+    escrow_account.resize(escrow_account.dutch(escrow), escrow_account.deposit - args.amount)?;
+
+    // Takes and approvals of the previous terms are rejected
+    escrow_account.version += 1;
+
+    Ok(())
+}
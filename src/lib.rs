@@ -1,14 +1,25 @@
+use error::EscrowError;
 use instructions::EscrowInstructions;
-use pinocchio::{account_info::AccountInfo, entrypoint, program_error::ProgramError, pubkey::Pubkey, ProgramResult};
+#[cfg(not(feature = "no-entrypoint"))]
+use pinocchio::entrypoint;
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey, ProgramResult};
 
+#[cfg(any(test, feature = "client"))]
+pub mod client;
+mod error;
+mod hash;
 mod instructions;
+mod metadata;
 mod state;
+mod token;
+#[cfg(test)]
 mod tests;
 
 
 use instructions::*;
-use state::*;
+use state::Config;
 
+#[cfg(not(feature = "no-entrypoint"))]
 entrypoint!(process_instruction);
 
 const ID: Pubkey = five8_const::decode_32_const("22222222222222222222222222222222222222222222");
@@ -18,14 +29,49 @@ pub fn process_instruction(
     accounts: &[AccountInfo],
     data: &[u8],
 ) -> ProgramResult{
-    assert_eq!(program_id, &crate::ID);
+    if program_id != &crate::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let (discriminator, data) = data.split_first().ok_or(ProgramError::InvalidInstructionData)?;
+
+    let instruction = EscrowInstructions::try_from(discriminator)?;
 
-    let (discriminator, data) = data.split_first().ok_or(ProgramError::InvalidAccountData)?;
+    if !instruction.has_args() && !data.is_empty() {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    // Makes and takes are refused while paused, refunds always go through so users can exit
+    if let Some(index) = instruction.config_index() {
+        let config = accounts.get(index).ok_or(ProgramError::NotEnoughAccountKeys)?;
+        if Config::from_account_info(config)?.is_paused() {
+            return Err(EscrowError::Paused.into());
+        }
+    }
 
-    match EscrowInstructions::try_from(discriminator)? {
-        EscrowInstructions::Make => process_make_instruction(accounts, data)?,
-        EscrowInstructions::Take => process_take_instruction(accounts, data)?,
+    match instruction {
+        EscrowInstructions::Make => process_make_instruction(accounts, MakeArgs::try_from(data)?)?,
+        EscrowInstructions::Take => process_take_instruction(accounts, TakeArgs::try_from(data)?)?,
         EscrowInstructions::Refund => process_refund_instruction(accounts, data)?,
+        EscrowInstructions::Update => process_update_instruction(accounts, UpdateArgs::try_from(data)?)?,
+        EscrowInstructions::Deposit => process_deposit_instruction(accounts, DepositArgs::try_from(data)?)?,
+        EscrowInstructions::Withdraw => process_withdraw_instruction(accounts, WithdrawArgs::try_from(data)?)?,
+        EscrowInstructions::MakeBasket => process_make_basket_instruction(accounts, MakeBasketArgs::try_from(data)?)?,
+        EscrowInstructions::TakeBasket => process_take_basket_instruction(accounts, data)?,
+        EscrowInstructions::RefundBasket => process_refund_basket_instruction(accounts, data)?,
+        EscrowInstructions::InitConfig => process_init_config_instruction(accounts, InitConfigArgs::try_from(data)?)?,
+        EscrowInstructions::SetPaused => process_set_paused_instruction(accounts, SetPausedArgs::try_from(data)?)?,
+        EscrowInstructions::SetAdmin => process_set_admin_instruction(accounts, SetAdminArgs::try_from(data)?)?,
+        EscrowInstructions::Dispute => process_dispute_instruction(accounts, data)?,
+        EscrowInstructions::Resolve => process_resolve_instruction(accounts, ResolveArgs::try_from(data)?)?,
+        EscrowInstructions::InitApprovals => process_init_approvals_instruction(accounts, InitApprovalsArgs::try_from(data)?)?,
+        EscrowInstructions::Approve => process_approve_instruction(accounts, ApproveArgs::try_from(data)?)?,
+        EscrowInstructions::Claim => process_claim_instruction(accounts, ClaimArgs::try_from(data)?)?,
+        EscrowInstructions::ClaimVested => process_claim_vested_instruction(accounts, data)?,
+        EscrowInstructions::Bid => process_bid_instruction(accounts, BidArgs::try_from(data)?)?,
+        EscrowInstructions::Settle => process_settle_instruction(accounts, data)?,
+        EscrowInstructions::Match => process_match_instruction(accounts, MatchArgs::try_from(data)?)?,
+        EscrowInstructions::Expire => process_expire_instruction(accounts, data)?,
     }
 
 
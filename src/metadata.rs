@@ -0,0 +1,92 @@
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
+
+use crate::error::EscrowError;
+
+pub const METADATA_PROGRAM_ID: Pubkey = five8_const::decode_32_const("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+
+/// `Key::MetadataV1`, the first byte of a Metaplex metadata account.
+const METADATA_V1: u8 = 4;
+
+/// Reads the verified collection of `mint` out of its Metaplex metadata account,
+/// `None` if the NFT has no collection or it isn't verified.
+///
+/// Metadata accounts are only ever written by the metadata program, which keeps
+/// one per mint, so checking the owner and the mint field is enough to trust it.
+pub fn verified_collection(metadata: &AccountInfo, mint: &Pubkey) -> Result<Option<Pubkey>, ProgramError> {
+    if metadata.owner() != &METADATA_PROGRAM_ID {
+        return Err(EscrowError::InvalidMetadata.into());
+    }
+
+    let data = unsafe { metadata.borrow_data_unchecked() };
+    let mut reader = Reader { data, offset: 0 };
+
+    // key, update_authority, mint
+    if reader.u8()? != METADATA_V1 {
+        return Err(EscrowError::InvalidMetadata.into());
+    }
+    reader.skip(32)?;
+    if reader.bytes(32)? != mint.as_slice() {
+        return Err(EscrowError::InvalidMetadata.into());
+    }
+
+    // name, symbol, uri, seller_fee_basis_points
+    for _ in 0..3 {
+        let len = reader.u32()? as usize;
+        reader.skip(len)?;
+    }
+    reader.skip(2)?;
+
+    // creators: Option<Vec<Creator { address, verified, share }>>
+    if reader.u8()? == 1 {
+        let len = reader.u32()? as usize;
+        reader.skip(len * (32 + 1 + 1))?;
+    }
+
+    // primary_sale_happened, is_mutable
+    reader.skip(2)?;
+
+    // edition_nonce: Option<u8>, token_standard: Option<u8>
+    for _ in 0..2 {
+        if reader.u8()? == 1 {
+            reader.skip(1)?;
+        }
+    }
+
+    // collection: Option<Collection { verified, key }>
+    if reader.u8()? != 1 {
+        return Ok(None);
+    }
+    let verified = reader.u8()? == 1;
+    let key: Pubkey = reader.bytes(32)?.try_into().map_err(|_| EscrowError::InvalidMetadata)?;
+
+    Ok(verified.then_some(key))
+}
+
+// Borsh reader failing on truncated data instead of reading past its end.
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], ProgramError> {
+        let bytes = self.data
+            .get(self.offset..self.offset.saturating_add(len))
+            .ok_or(EscrowError::InvalidMetadata)?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> Result<(), ProgramError> {
+        self.bytes(len).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Result<u8, ProgramError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, ProgramError> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
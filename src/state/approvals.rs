@@ -0,0 +1,59 @@
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey::{checked_create_program_address, Pubkey}};
+
+use crate::error::EscrowError;
+
+/// Maximum number of approvers of a multisig escrow, one bit each in `Approvals::approved`.
+pub const MAX_APPROVERS: usize = 8;
+
+/// Approvers of a `MULTISIG` escrow, held in the PDA `["approvals", escrow]`.
+/// Take only releases the vault once `threshold` of them approved the current terms.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Approvals {
+    pub escrow: Pubkey,
+    pub approvers: [Pubkey; MAX_APPROVERS],
+    /// Escrow `version` the approvals were given for, they lapse when the maker updates the terms.
+    pub version: u64,
+    pub approvers_len: u8,
+    pub threshold: u8,
+    /// Bit `i` is set once `approvers[i]` approved.
+    pub approved: u8,
+    pub bump: u8,
+    /// Keeps the account as long as the struct.
+    pub padding: [u8; 4],
+}
+
+impl Approvals {
+    pub const LEN: usize = 32 + 32 * MAX_APPROVERS + 8 + 1 + 1 + 1 + 1 + 4;
+
+    pub fn approvers(&self) -> &[Pubkey] {
+        &self.approvers[..self.approvers_len as usize]
+    }
+
+    /// Whether enough approvers approved the escrow terms at `version`.
+    pub fn is_approved(&self, version: u64) -> bool {
+        self.version == version && self.approved.count_ones() >= self.threshold as u32
+    }
+
+    #[allow(clippy::mut_from_ref)]
+    pub fn from_account_info_unchecked(account_info: &AccountInfo) -> &mut Self {
+        unsafe {&mut *(account_info.borrow_mut_data_unchecked().as_ptr() as *mut Self)}
+    }
+
+    /// Loads the approvals of `escrow`, checking they are its approvals PDA.
+    #[allow(clippy::mut_from_ref)]
+    pub fn from_account_info<'a>(account_info: &'a AccountInfo, escrow: &Pubkey) -> Result<&'a mut Self, ProgramError> {
+        if account_info.data_len() != Approvals::LEN || account_info.owner() != &crate::ID {
+            return Err(EscrowError::InvalidApprovals.into());
+        }
+        let approvals = unsafe { &mut *(account_info.borrow_mut_data_unchecked().as_ptr() as *mut Self) };
+
+        let bump = [approvals.bump];
+        let pda = checked_create_program_address(&[b"approvals", escrow.as_ref(), bump.as_ref()], &crate::ID)
+            .map_err(|_| EscrowError::InvalidApprovals)?;
+        if account_info.key() != &pda || &approvals.escrow != escrow {
+            return Err(EscrowError::InvalidApprovals.into());
+        }
+        Ok(approvals)
+    }
+}
@@ -0,0 +1,78 @@
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey::{checked_create_program_address, Pubkey}};
+
+use super::check_vault_address;
+use crate::error::EscrowError;
+
+/// Maximum number of mints on each side of a basket.
+pub const MAX_BASKET_LEGS: usize = 4;
+
+/// One mint of a basket and its amount.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BasketLeg {
+    pub mint: Pubkey,
+    pub amount: u64,
+}
+
+/// Escrow trading several mints for several others, settled all at once.
+/// Each offered mint is held in its own vault derived like an escrow vault.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Basket {
+    pub maker: Pubkey,
+    pub taker: Pubkey,
+    pub seed: u64,
+    pub expiry: i64,
+    pub offered: [BasketLeg; MAX_BASKET_LEGS],
+    pub requested: [BasketLeg; MAX_BASKET_LEGS],
+    pub offered_len: u8,
+    pub requested_len: u8,
+    pub bump: u8,
+    pub vault_bumps: [u8; MAX_BASKET_LEGS],
+    /// Keeps the account as long as the struct.
+    pub padding: [u8; 1],
+}
+
+impl Basket {
+    pub const LEN: usize = 32 + 32 + 8 + 8 + (32 + 8) * MAX_BASKET_LEGS * 2 + 1 + 1 + 1 + MAX_BASKET_LEGS + 1;
+
+    /// The mints deposited by the maker, with the amounts held in their vaults.
+    pub fn offered(&self) -> &[BasketLeg] {
+        &self.offered[..self.offered_len as usize]
+    }
+
+    /// The mints requested by the maker, with the amounts the taker pays.
+    pub fn requested(&self) -> &[BasketLeg] {
+        &self.requested[..self.requested_len as usize]
+    }
+
+    /// Checks that `basket` is the address of this basket, the PDA `["basket", maker, seed]`.
+    pub fn check_address(&self, basket: &Pubkey, maker: &Pubkey) -> Result<(), ProgramError> {
+        let seed = self.seed.to_le_bytes();
+        let bump = [self.bump];
+        let seeds = [(b"basket"), maker.as_slice(), seed.as_ref(), bump.as_ref()];
+        let pda = checked_create_program_address(&seeds, &crate::ID).map_err(|_| EscrowError::InvalidEscrowAddress)?;
+        if *basket != pda {
+            return Err(EscrowError::InvalidEscrowAddress.into());
+        }
+        Ok(())
+    }
+
+    /// Checks that `vault` is the vault of the `i`th offered mint of this basket.
+    pub fn check_vault(&self, i: usize, vault: &Pubkey, basket: &Pubkey, mint: &Pubkey) -> Result<(), ProgramError> {
+        check_vault_address(vault, basket, mint, self.vault_bumps[i])
+    }
+
+    #[allow(clippy::mut_from_ref)]
+    pub fn from_account_info_unchecked(account_info: &AccountInfo) -> &mut Self {
+        unsafe {&mut *(account_info.borrow_mut_data_unchecked().as_ptr() as *mut Self)}
+    }
+
+    #[allow(clippy::mut_from_ref)]
+    pub fn from_account_info(account_info: &AccountInfo) -> Result<&mut Self, ProgramError> {
+        if account_info.data_len() != Basket::LEN || account_info.owner() != &crate::ID {
+            return Err(EscrowError::InvalidEscrowAccount.into());
+        }
+        Ok(unsafe { &mut *(account_info.borrow_mut_data_unchecked().as_ptr() as *mut Self) })
+    }
+}
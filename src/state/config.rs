@@ -0,0 +1,58 @@
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey::{checked_create_program_address, Pubkey}};
+
+use crate::error::EscrowError;
+
+/// Global settings of the program, held in the PDA `["config"]`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Config {
+    pub admin: Pubkey,
+    /// Owner of the token accounts receiving the protocol fee.
+    pub treasury: Pubkey,
+    /// Protocol fee taken out of every Take payment, in basis points.
+    pub fee_bps: u16,
+    /// Highest referrer fee a taker can grant, in basis points.
+    pub max_referrer_bps: u16,
+    /// Non-zero while the admin has paused makes and takes.
+    pub paused: u8,
+    pub bump: u8,
+}
+
+/// `bps` basis points of `amount`, rounded down.
+pub fn bps_of(amount: u64, bps: u16) -> u64 {
+    (amount as u128 * bps as u128 / 10_000) as u64
+}
+
+impl Config {
+    pub const LEN: usize = 32 + 32 + 2 + 2 + 1 + 1;
+
+    /// Protocol fee charged when paying `amount`, rounded down.
+    pub fn fee(&self, amount: u64) -> u64 {
+        bps_of(amount, self.fee_bps)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused != 0
+    }
+
+    #[allow(clippy::mut_from_ref)]
+    pub fn from_account_info_unchecked(account_info: &AccountInfo) -> &mut Self {
+        unsafe {&mut *(account_info.borrow_mut_data_unchecked().as_ptr() as *mut Self)}
+    }
+
+    /// Loads the config, checking it is the initialized config PDA.
+    #[allow(clippy::mut_from_ref)]
+    pub fn from_account_info(account_info: &AccountInfo) -> Result<&mut Self, ProgramError> {
+        if account_info.data_len() != Config::LEN || account_info.owner() != &crate::ID {
+            return Err(EscrowError::InvalidConfig.into());
+        }
+        let config = unsafe { &mut *(account_info.borrow_mut_data_unchecked().as_ptr() as *mut Self) };
+
+        let bump = [config.bump];
+        let pda = checked_create_program_address(&[b"config", bump.as_ref()], &crate::ID).map_err(|_| EscrowError::InvalidConfig)?;
+        if account_info.key() != &pda {
+            return Err(EscrowError::InvalidConfig.into());
+        }
+        Ok(config)
+    }
+}
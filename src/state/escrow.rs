@@ -1,26 +1,353 @@
-use pinocchio::{account_info::AccountInfo, pubkey::Pubkey};
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey::{checked_create_program_address, Pubkey}};
 
+use crate::error::EscrowError;
+
+/// `Escrow::flags` bit set when mint_x is traded as an NFT.
+pub const NFT_X: u8 = 1 << 0;
+/// `Escrow::flags` bit set when mint_y is traded as an NFT.
+pub const NFT_Y: u8 = 1 << 1;
+/// `Escrow::flags` bit set once the escrow has approvers, see `Approvals`.
+pub const MULTISIG: u8 = 1 << 2;
+/// `Escrow::flags` bit of a hash time-locked escrow, released by `Claim` instead of `Take`.
+pub const HTLC: u8 = 1 << 3;
+/// `Escrow::flags` bit of a vesting escrow, paid out to its taker by `ClaimVested`.
+pub const VESTING: u8 = 1 << 4;
+/// `Escrow::flags` bit of an English auction, bid on with `Bid` and closed by `Settle`.
+pub const AUCTION: u8 = 1 << 5;
+/// `Escrow::flags` bit of a Dutch auction, whose requested amount decays to a floor over time.
+pub const DUTCH: u8 = 1 << 6;
+
+/// `Escrow::status` of an escrow that can be taken or refunded.
+pub const STATUS_OPEN: u8 = 0;
+/// `Escrow::status` while a dispute waits for the arbiter.
+pub const STATUS_DISPUTED: u8 = 1;
+
+/// Checks that `vault` is the vault of `mint` held by `owner`, an escrow or a basket,
+/// at the PDA `["vault", owner, mint]` with `bump`.
+pub fn check_vault_address(vault: &Pubkey, owner: &Pubkey, mint: &Pubkey, bump: u8) -> Result<(), ProgramError> {
+    let bump = [bump];
+    let seeds = [(b"vault"), owner.as_slice(), mint.as_slice(), bump.as_ref()];
+    let pda = checked_create_program_address(&seeds, &crate::ID).map_err(|_| EscrowError::InvalidVaultAddress)?;
+    if *vault != pda {
+        return Err(EscrowError::InvalidVaultAddress.into());
+    }
+    Ok(())
+}
+
+/// Checks that `bid_vault` is the vault of the `mint_y` bids on the auction `escrow`,
+/// at the PDA `["bid_vault", escrow, mint_y]` with `bump`.
+pub fn check_bid_vault_address(bid_vault: &Pubkey, escrow: &Pubkey, mint_y: &Pubkey, bump: u8) -> Result<(), ProgramError> {
+    let bump = [bump];
+    let seeds = [(b"bid_vault"), escrow.as_slice(), mint_y.as_slice(), bump.as_ref()];
+    let pda = checked_create_program_address(&seeds, &crate::ID).map_err(|_| EscrowError::InvalidVaultAddress)?;
+    if *bid_vault != pda {
+        return Err(EscrowError::InvalidVaultAddress.into());
+    }
+    Ok(())
+}
+
+/// Escrow account, followed by the section of its mode: `Htlc`, `Vesting`, `Auction` or
+/// `Dutch`. A regular trade has no section.
 #[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct Escrow{
     pub maker: Pubkey,
     pub mint_x: Pubkey,
     pub mint_y: Pubkey,
+    pub taker: Pubkey,
+    pub collection: Pubkey,
+    /// Third party settling disputes, the default public key for none.
+    pub arbiter: Pubkey,
+    pub seed: u64,
     pub amount: u64,
+    pub deposit: u64,
+    pub expiry: i64,
+    pub version: u64,
     pub bump: u8,
+    pub vault_bump: u8,
+    pub flags: u8,
+    pub status: u8,
+    /// Keeps the section that follows 8-byte aligned.
+    pub padding: [u8; 4],
+}
+
+/// Section of an `HTLC` escrow.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Htlc {
+    /// SHA-256 of the preimage releasing the escrow.
+    pub hashlock: [u8; 32],
+}
+
+impl Htlc {
+    pub const LEN: usize = 32;
+}
+
+/// Section of a `VESTING` escrow.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Vesting {
+    /// Unix timestamp the deposit starts unlocking from.
+    pub start: i64,
+    /// Unix timestamp before which nothing is unlocked, `0` for none.
+    pub cliff: i64,
+    /// Unix timestamp at which the deposit is fully unlocked.
+    pub end: i64,
+    /// Amount of the deposit already paid out.
+    pub claimed: u64,
+}
+
+impl Vesting {
+    pub const LEN: usize = 8 + 8 + 8 + 8;
+
+    /// Amount of `deposit` unlocked at `now`, growing linearly from `start` to `end`
+    /// and rounded down.
+    pub fn vested(&self, deposit: u64, now: i64) -> u64 {
+        if now < self.start.max(self.cliff) {
+            return 0;
+        }
+        if now >= self.end {
+            return deposit;
+        }
+
+        // `abs_diff` can't overflow on timestamps far apart, and `now` is past the start
+        let elapsed = now.abs_diff(self.start) as u128;
+        let duration = self.end.abs_diff(self.start) as u128;
+        (deposit as u128 * elapsed / duration) as u64
+    }
+}
+
+/// Section of an `AUCTION` escrow, whose reserve price is `Escrow::amount`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Auction {
+    /// Highest bidder, the default public key before the first bid.
+    pub bidder: Pubkey,
+    /// Smallest raise over the highest bid.
+    pub min_increment: u64,
+    /// Highest bid, held in the bid vault.
+    pub bid: u64,
+    /// The bump of the bid vault PDA.
+    pub bid_vault_bump: u8,
+    /// Keeps the section as long as the struct.
+    pub padding: [u8; 7],
+}
+
+impl Auction {
+    pub const LEN: usize = 32 + 8 + 8 + 1 + 7;
+
+    /// Whether the auction has received a bid.
+    pub fn has_bid(&self) -> bool {
+        self.bidder != Pubkey::default()
+    }
+
+    /// Checks that `bid_vault` is the bid vault of this auction holding `mint_y`.
+    pub fn check_bid_vault(&self, bid_vault: &Pubkey, escrow: &Pubkey, mint_y: &Pubkey) -> Result<(), ProgramError> {
+        check_bid_vault_address(bid_vault, escrow, mint_y, self.bid_vault_bump)
+    }
+}
+
+/// Section of a `DUTCH` escrow, whose start price is `Escrow::amount`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Dutch {
+    /// Amount of mint_y asked for at the end of the decay.
+    pub floor: u64,
+    /// Unix timestamp the price starts decaying from.
+    pub start: i64,
+    /// Unix timestamp at which the price reaches its floor.
+    pub end: i64,
+    /// Seconds between price drops, `0` for a linear decay.
+    pub step: i64,
+}
+
+impl Dutch {
+    pub const LEN: usize = 8 + 8 + 8 + 8;
+
+    /// Amount of mint_y asked for at `now`, dropping from `amount` to `floor` between
+    /// `start` and `end`, every `step` seconds or continuously, rounded up.
+    pub fn price(&self, amount: u64, now: i64) -> u64 {
+        if now <= self.start {
+            return amount;
+        }
+        if now >= self.end {
+            return self.floor;
+        }
+
+        // `abs_diff` can't overflow on timestamps far apart, and `now` is past the start
+        let mut elapsed = now.abs_diff(self.start);
+        if self.step > 0 {
+            elapsed -= elapsed % self.step as u64;
+        }
+        let duration = self.end.abs_diff(self.start);
+        let decay = amount.saturating_sub(self.floor) as u128 * elapsed as u128 / duration as u128;
+        amount - decay as u64
+    }
 }
 
 impl Escrow{
-    pub const LEN: usize = 32 + 32 + 32 + 8 + 1;
+    pub const LEN: usize = 32 + 32 + 32 + 32 + 32 + 32 + 8 + 8 + 8 + 8 + 8 + 1 + 1 + 1 + 1 + 4;
+
+    /// Size of an escrow account with `flags`, the escrow followed by the section of its mode.
+    pub fn space(flags: u8) -> usize {
+        Escrow::LEN + if flags & HTLC != 0 {
+            Htlc::LEN
+        } else if flags & VESTING != 0 {
+            Vesting::LEN
+        } else if flags & AUCTION != 0 {
+            Auction::LEN
+        } else if flags & DUTCH != 0 {
+            Dutch::LEN
+        } else {
+            0
+        }
+    }
 
+    /// Whether the maker offers lamports, held by the escrow account, instead of mint_x tokens.
+    pub fn offers_sol(&self) -> bool {
+        self.mint_x == pinocchio_system::ID
+    }
+
+    /// Whether the maker asks for lamports instead of mint_y tokens.
+    pub fn requests_sol(&self) -> bool {
+        self.mint_y == pinocchio_system::ID
+    }
+
+    /// Whether mint_x is an NFT, deposited as a single token.
+    pub fn offers_nft(&self) -> bool {
+        self.flags & NFT_X != 0
+    }
+
+    /// Whether mint_y is an NFT, paid as a single token.
+    pub fn requests_nft(&self) -> bool {
+        self.flags & NFT_Y != 0
+    }
+
+    /// Whether Take needs the approvals of the escrow approvers.
+    pub fn is_multisig(&self) -> bool {
+        self.flags & MULTISIG != 0
+    }
+
+    /// Whether the escrow is hash time-locked: claimed by the taker with the preimage
+    /// of `Htlc::hashlock` before `expiry`, refunded after.
+    pub fn is_htlc(&self) -> bool {
+        self.flags & HTLC != 0
+    }
+
+    /// Whether the escrow is a vesting grant unlocking its deposit to the taker over time.
+    pub fn is_vesting(&self) -> bool {
+        self.flags & VESTING != 0
+    }
+
+    /// Whether the escrow is an English auction of its deposit.
+    pub fn is_auction(&self) -> bool {
+        self.flags & AUCTION != 0
+    }
+
+    /// Whether the escrow is a regular trade, filled by Take and changed by the maker.
+    pub fn is_trade(&self) -> bool {
+        self.flags & (HTLC | VESTING | AUCTION) == 0
+    }
+
+    /// Whether the requested amount decays over time as a Dutch auction.
+    pub fn is_dutch(&self) -> bool {
+        self.flags & DUTCH != 0
+    }
+
+    /// Whether an arbiter can settle disputes on the escrow.
+    pub fn has_arbiter(&self) -> bool {
+        self.arbiter != Pubkey::default()
+    }
+
+    /// Whether a dispute is open, in which case only the arbiter can settle the escrow.
+    pub fn is_disputed(&self) -> bool {
+        self.status == STATUS_DISPUTED
+    }
+
+    /// Resizes the deposit to `deposit`, scaling the requested amount, and the floor of
+    /// a Dutch auction, by the same factor so the price stays the same, rounded up in
+    /// favour of the maker.
+    pub fn resize(&mut self, dutch: Option<&mut Dutch>, deposit: u64) -> Result<(), ProgramError> {
+        if self.deposit == 0 {
+            return Err(EscrowError::InvalidAmount.into());
+        }
+        let scale = |amount: u64| {
+            let amount = (amount as u128 * deposit as u128).div_ceil(self.deposit as u128);
+            u64::try_from(amount).map_err(|_| EscrowError::InvalidAmount)
+        };
+
+        self.amount = scale(self.amount)?;
+        if let Some(dutch) = dutch {
+            dutch.floor = scale(dutch.floor)?;
+        }
+        self.deposit = deposit;
+        Ok(())
+    }
+
+    /// Checks that `escrow` is the address of this escrow, the PDA `["escrow", maker, seed]`.
+    pub fn check_address(&self, escrow: &Pubkey, maker: &Pubkey) -> Result<(), ProgramError> {
+        let seed = self.seed.to_le_bytes();
+        let bump = [self.bump];
+        let seeds = [(b"escrow"), maker.as_slice(), seed.as_ref(), bump.as_ref()];
+        let pda = checked_create_program_address(&seeds, &crate::ID).map_err(|_| EscrowError::InvalidEscrowAddress)?;
+        if *escrow != pda {
+            return Err(EscrowError::InvalidEscrowAddress.into());
+        }
+        Ok(())
+    }
+
+    /// Checks that `vault` is the vault of this escrow holding `mint_x`.
+    pub fn check_vault(&self, vault: &Pubkey, escrow: &Pubkey, mint_x: &Pubkey) -> Result<(), ProgramError> {
+        check_vault_address(vault, escrow, mint_x, self.vault_bump)
+    }
+
+    /// The section of an `HTLC` escrow, read from its `account_info`.
+    #[allow(clippy::mut_from_ref)]
+    pub fn htlc<'a>(&self, account_info: &'a AccountInfo) -> Option<&'a mut Htlc> {
+        self.section(account_info, self.is_htlc())
+    }
+
+    /// The section of a `VESTING` escrow, read from its `account_info`.
+    #[allow(clippy::mut_from_ref)]
+    pub fn vesting<'a>(&self, account_info: &'a AccountInfo) -> Option<&'a mut Vesting> {
+        self.section(account_info, self.is_vesting())
+    }
+
+    /// The section of an `AUCTION` escrow, read from its `account_info`.
+    #[allow(clippy::mut_from_ref)]
+    pub fn auction<'a>(&self, account_info: &'a AccountInfo) -> Option<&'a mut Auction> {
+        self.section(account_info, self.is_auction())
+    }
+
+    /// The section of a `DUTCH` escrow, read from its `account_info`.
+    #[allow(clippy::mut_from_ref)]
+    pub fn dutch<'a>(&self, account_info: &'a AccountInfo) -> Option<&'a mut Dutch> {
+        self.section(account_info, self.is_dutch())
+    }
+
+    // The section follows the escrow in its account, `None` for another mode
+    #[allow(clippy::mut_from_ref)]
+    fn section<'a, T>(&self, account_info: &'a AccountInfo, is_mode: bool) -> Option<&'a mut T> {
+        if !is_mode || account_info.data_len() != Escrow::space(self.flags) {
+            return None;
+        }
+        Some(unsafe { &mut *(account_info.borrow_mut_data_unchecked().as_ptr().add(Escrow::LEN) as *mut T) })
+    }
+
+    #[allow(clippy::mut_from_ref)]
     pub fn from_account_info_unchecked(account_info: &AccountInfo) -> &mut Self {
         unsafe {&mut *(account_info.borrow_mut_data_unchecked().as_ptr() as *mut Self)}
     }
 
-    pub fn from_account_info(account_info: &AccountInfo) -> &mut Self {
-        unsafe {
-            assert_eq!(account_info.data_len(), Escrow::LEN);
-            assert_eq!(account_info.owner(), &crate::ID);
-            &mut *(account_info.borrow_mut_data_unchecked().as_ptr() as *mut Self)
+    #[allow(clippy::mut_from_ref)]
+    pub fn from_account_info(account_info: &AccountInfo) -> Result<&mut Self, ProgramError> {
+        if account_info.data_len() < Escrow::LEN || account_info.owner() != &crate::ID {
+            return Err(EscrowError::InvalidEscrowAccount.into());
+        }
+        let escrow = unsafe { &mut *(account_info.borrow_mut_data_unchecked().as_ptr() as *mut Self) };
+        if account_info.data_len() != Escrow::space(escrow.flags) {
+            return Err(EscrowError::InvalidEscrowAccount.into());
         }
+        Ok(escrow)
     }
 }
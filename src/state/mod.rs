@@ -1,3 +1,9 @@
+pub mod approvals;
+pub mod basket;
+pub mod config;
 pub mod escrow;
 
+pub use approvals::*;
+pub use basket::*;
+pub use config::*;
 pub use escrow::*;
\ No newline at end of file
@@ -1,17 +1,240 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod escrow_tests{
     use mollusk_svm::{program, result::Check, Mollusk};
     use solana_sdk::{
-        account::{AccountSharedData, WritableAccount}, instruction::{AccountMeta, Instruction}, native_token::LAMPORTS_PER_SOL, program_option::COption, program_pack::Pack, pubkey::{self, Pubkey}, rent::Rent, system_program, sysvar::Sysvar
+        account::{AccountSharedData, ReadableAccount, WritableAccount}, bpf_loader_upgradeable, instruction::{AccountMeta, Instruction}, native_token::LAMPORTS_PER_SOL, program_error::ProgramError, program_option::COption, program_pack::Pack, pubkey::{self, Pubkey}
     };
     use spl_token::state::AccountState;
+    use spl_token_2022::extension::{
+        transfer_fee::{TransferFee, TransferFeeAmount, TransferFeeConfig}, BaseStateWithExtensionsMut, ExtensionType, StateWithExtensionsMut
+    };
 
-    use crate::state::escrow;
+    use crate::{
+        client::{
+            self, AuctionParams, BasketDeposit, BasketPayment, BasketReceipt, BasketRequest, BidParams, ClaimParams, ClaimVestedParams, InitApprovalsParams, InitConfigParams, MakeBasketParams, MakeParams, MatchParams, RefundBasketParams,
+            RefundParams, ResizeParams, Resolution, ResolveParams, TakeBasketParams, SettleParams, TakeParams, UpdateParams, VestingSchedule,
+        },
+        error::EscrowError,
+        state::{Approvals, Auction, Basket, BasketLeg, Config, Dutch, Escrow, Htlc, Vesting, AUCTION, DUTCH, HTLC, NFT_X, NFT_Y, STATUS_DISPUTED, VESTING},
+    };
 
     const ID: Pubkey = pubkey::Pubkey::new_from_array(five8_const::decode_32_const(
         "22222222222222222222222222222222222222222222"
     ));
 
+    // Creates the harness with the escrow and SPL token programs loaded and
+    // returns it together with the system and token program accounts.
+    fn setup() -> (Mollusk, (Pubkey, AccountSharedData), (Pubkey, AccountSharedData)) {
+        let mut mollusk = Mollusk::new(&ID, "target/release/libescrow_pinocchio");
+
+        mollusk.add_program(
+            &spl_token::ID,
+            "programs/spl_token-3.5.0",
+            &mollusk_svm::program::loader_keys::LOADER_V3,
+        );
+
+        let system = program::keyed_account_for_system_program();
+        let token = (
+            spl_token::ID,
+            program::create_program_account_loader_v3(&spl_token::ID),
+        );
+
+        (mollusk, system, token)
+    }
+
+    fn mint_account(mollusk: &Mollusk) -> AccountSharedData {
+        let mut account = AccountSharedData::new(
+            mollusk.sysvars.rent.minimum_balance(spl_token::state::Mint::LEN),
+            spl_token::state::Mint::LEN,
+            &spl_token::ID,
+        );
+        solana_sdk::program_pack::Pack::pack(
+            spl_token::state::Mint {
+                mint_authority: COption::None,
+                supply: 100_000_000,
+                decimals: 6,
+                is_initialized: true,
+                freeze_authority: COption::None,
+            },
+            account.data_as_mut_slice(),
+        )
+        .unwrap();
+        account
+    }
+
+    // Creates an NFT mint, with 0 decimals and a supply of 1.
+    fn nft_mint_account(mollusk: &Mollusk) -> AccountSharedData {
+        let mut account = mint_account(mollusk);
+        solana_sdk::program_pack::Pack::pack(
+            spl_token::state::Mint {
+                mint_authority: COption::None,
+                supply: 1,
+                decimals: 0,
+                is_initialized: true,
+                freeze_authority: COption::None,
+            },
+            account.data_as_mut_slice(),
+        )
+        .unwrap();
+        account
+    }
+
+    // Creates the Metaplex metadata of `mint`, listing `collection` and whether it is verified.
+    fn metadata_account(mint: Pubkey, collection: Pubkey, verified: bool) -> AccountSharedData {
+        let mut data = vec![4];
+        data.extend_from_slice(&[0x01; 32]);
+        data.extend_from_slice(mint.as_ref());
+        for field in [&b"Escrow NFT"[..], b"ENFT", b"https://example.com/nft.json"] {
+            data.extend_from_slice(&(field.len() as u32).to_le_bytes());
+            data.extend_from_slice(field);
+        }
+        data.extend_from_slice(&500u16.to_le_bytes());
+        // No creators, primary sale not happened, mutable, no edition nonce, non-fungible token standard
+        data.extend_from_slice(&[0, 0, 1, 0, 1, 0]);
+        data.extend_from_slice(&[1, verified as u8]);
+        data.extend_from_slice(collection.as_ref());
+
+        let mut account = AccountSharedData::new(LAMPORTS_PER_SOL, data.len(), &client::METADATA_PROGRAM_ID);
+        account.set_data_from_slice(&data);
+        account
+    }
+
+    fn token_account(mollusk: &Mollusk, mint: Pubkey, owner: Pubkey, amount: u64) -> AccountSharedData {
+        let mut account = AccountSharedData::new(
+            mollusk.sysvars.rent.minimum_balance(spl_token::state::Account::LEN),
+            spl_token::state::Account::LEN,
+            &spl_token::ID,
+        );
+        solana_sdk::program_pack::Pack::pack(
+            spl_token::state::Account {
+                mint,
+                owner,
+                amount,
+                delegate: COption::None,
+                state: AccountState::Initialized,
+                is_native: COption::None,
+                delegated_amount: 0,
+                close_authority: COption::None,
+            },
+            account.data_as_mut_slice(),
+        )
+        .unwrap();
+        account
+    }
+
+    // Creates an escrow account owned by the program holding the given state.
+    fn escrow_account(mollusk: &Mollusk, state: &Escrow) -> AccountSharedData {
+        program_account(mollusk, &escrow_account_data(state))
+    }
+
+    fn escrow_account_data(state: &Escrow) -> Vec<u8> {
+        unsafe { core::slice::from_raw_parts(state as *const Escrow as *const u8, Escrow::LEN) }.to_vec()
+    }
+
+    // Creates an escrow account holding the given state followed by the section of its mode.
+    fn escrow_section_account<T>(mollusk: &Mollusk, state: &Escrow, section: &T) -> AccountSharedData {
+        program_account(mollusk, &escrow_section_data(state, section))
+    }
+
+    fn escrow_section_data<T>(state: &Escrow, section: &T) -> Vec<u8> {
+        let mut data = escrow_account_data(state);
+        data.extend_from_slice(unsafe { core::slice::from_raw_parts(section as *const T as *const u8, core::mem::size_of::<T>()) });
+        data
+    }
+
+    // Creates a rent-exempt account owned by the program holding `data`.
+    fn program_account(mollusk: &Mollusk, data: &[u8]) -> AccountSharedData {
+        let mut account = AccountSharedData::new(mollusk.sysvars.rent.minimum_balance(data.len()), data.len(), &ID);
+        account.data_as_mut_slice().copy_from_slice(data);
+        account
+    }
+
+    // Creates a Token-2022 mint charging `fee_bps` on every transfer.
+    fn mint_2022_account(mollusk: &Mollusk, fee_bps: u16) -> AccountSharedData {
+        let space = ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(&[ExtensionType::TransferFeeConfig]).unwrap();
+        let mut account = AccountSharedData::new(mollusk.sysvars.rent.minimum_balance(space), space, &spl_token_2022::ID);
+
+        let mut state = StateWithExtensionsMut::<spl_token_2022::state::Mint>::unpack_uninitialized(account.data_as_mut_slice()).unwrap();
+        let transfer_fee = TransferFee {
+            epoch: 0.into(),
+            maximum_fee: u64::MAX.into(),
+            transfer_fee_basis_points: fee_bps.into(),
+        };
+        let config = state.init_extension::<TransferFeeConfig>(true).unwrap();
+        config.older_transfer_fee = transfer_fee;
+        config.newer_transfer_fee = transfer_fee;
+        state.base = spl_token_2022::state::Mint {
+            mint_authority: COption::None,
+            supply: 100_000_000,
+            decimals: 6,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+        account
+    }
+
+    // Creates a Token-2022 account able to hold tokens of a mint with transfer fees.
+    fn token_2022_account(mollusk: &Mollusk, mint: Pubkey, owner: Pubkey, amount: u64) -> AccountSharedData {
+        let space = ExtensionType::try_calculate_account_len::<spl_token_2022::state::Account>(&[ExtensionType::TransferFeeAmount]).unwrap();
+        let mut account = AccountSharedData::new(mollusk.sysvars.rent.minimum_balance(space), space, &spl_token_2022::ID);
+
+        let mut state = StateWithExtensionsMut::<spl_token_2022::state::Account>::unpack_uninitialized(account.data_as_mut_slice()).unwrap();
+        state.init_extension::<TransferFeeAmount>(true).unwrap();
+        state.base = spl_token_2022::state::Account {
+            mint,
+            owner,
+            amount,
+            delegate: COption::None,
+            state: spl_token_2022::state::AccountState::Initialized,
+            is_native: COption::None,
+            delegated_amount: 0,
+            close_authority: COption::None,
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+        account
+    }
+
+    // Creates the config PDA charging `fee_bps` on takes, paid to `treasury`, and allowing
+    // referrer fees up to `max_referrer_bps`.
+    fn config_account(mollusk: &Mollusk, fee_bps: u16, max_referrer_bps: u16, treasury: Pubkey) -> (Pubkey, AccountSharedData) {
+        let (config, bump) = client::config_address();
+        let state = Config {
+            admin: [0x01; 32],
+            treasury: treasury.to_bytes(),
+            fee_bps,
+            max_referrer_bps,
+            bump,
+            ..Default::default()
+        };
+
+        let mut account = AccountSharedData::new(mollusk.sysvars.rent.minimum_balance(Config::LEN), Config::LEN, &ID);
+        account
+            .data_as_mut_slice()
+            .copy_from_slice(unsafe { core::slice::from_raw_parts(&state as *const Config as *const u8, Config::LEN) });
+        (config, account)
+    }
+
+    // Creates the program data account of the escrow program, upgradeable by `authority`.
+    fn program_data_account(authority: Pubkey) -> (Pubkey, AccountSharedData) {
+        let mut data = 3u32.to_le_bytes().to_vec();
+        data.extend_from_slice(&0u64.to_le_bytes());
+        data.push(1);
+        data.extend_from_slice(authority.as_ref());
+
+        let mut account = AccountSharedData::new(LAMPORTS_PER_SOL, data.len(), &bpf_loader_upgradeable::ID);
+        account.set_data_from_slice(&data);
+        (bpf_loader_upgradeable::get_program_data_address(&ID), account)
+    }
+
+    // Reads the balance of a token account of either token program.
+    fn token_amount(account: &AccountSharedData) -> u64 {
+        spl_token::state::Account::unpack(&account.data()[..spl_token::state::Account::LEN]).unwrap().amount
+    }
+
     #[test]
     fn test_make(){
         let mut mollusk = Mollusk::new(&ID, "target/release/libescrow_pinocchio");
@@ -30,9 +253,10 @@ mod escrow_tests{
         );
 
         let maker = Pubkey::new_from_array([0x02; 32]);
-        let maker_account = AccountSharedData::new(1 * LAMPORTS_PER_SOL, 0, &system_program);
+        let maker_account = AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program);
 
-        let (escrow, escrow_bump) = Pubkey::find_program_address(&[(b"escrow"), maker.as_ref()], &ID);
+        let seed = 42u64;
+        let (escrow, escrow_bump) = Pubkey::find_program_address(&[(b"escrow"), maker.as_ref(), &seed.to_le_bytes()], &ID);
         let escrow_account = AccountSharedData::new(0, 0, &system_program);
 
         let mint_x = Pubkey::new_from_array([0x03; 32]);
@@ -102,35 +326,22 @@ mod escrow_tests{
         )
         .unwrap();
 
-        let vault = Pubkey::new_from_array([0x06; 32]);
-        let mut vault_account = AccountSharedData::new(
-            mollusk
-                .sysvars
-                .rent
-                .minimum_balance(spl_token::state::Account::LEN),
-            spl_token::state::Account::LEN,
-            &token_program,
-        );
-        solana_sdk::program_pack::Pack::pack(
-            spl_token::state::Account {
-                mint: mint_x,
-                owner: escrow,
-                amount: 0,
-                delegate: COption::None,
-                state: AccountState::Initialized,
-                is_native: COption::None,
-                delegated_amount: 0,
-                close_authority: COption::None,
-            },
-            vault_account.data_as_mut_slice(),
-        )
-        .unwrap();
+        // Make creates the vault at the PDA ["vault", escrow, mint_x].
+        let (vault, vault_bump) = Pubkey::find_program_address(&[(b"vault"), escrow.as_ref(), mint_x.as_ref()], &ID);
+        let vault_account = AccountSharedData::new(0, 0, &system_program);
 
         let data = [
             vec![0],
             vec![escrow_bump],
+            seed.to_le_bytes().to_vec(),
             1_000_000u64.to_le_bytes().to_vec(),
             1_000_000u64.to_le_bytes().to_vec(),
+            0i64.to_le_bytes().to_vec(), // no expiry
+            Pubkey::default().as_ref().to_vec(), // anyone can take
+            vec![vault_bump],
+            vec![0], // flags
+            Pubkey::default().as_ref().to_vec(), // no collection
+            Pubkey::default().as_ref().to_vec(), // no arbiter
         ]
         .concat();
 
@@ -146,12 +357,25 @@ mod escrow_tests{
                 AccountMeta::new(escrow, true),
                 AccountMeta::new_readonly(system_program, false),
                 AccountMeta::new_readonly(token_program, false),
+                AccountMeta::new_readonly(client::config_address().0, false),
             ],
         );
 
-        mollusk.process_and_validate_instruction(
+        let expected = Escrow {
+            maker: maker.to_bytes(),
+            mint_x: mint_x.to_bytes(),
+            mint_y: mint_y.to_bytes(),
+            seed,
+            amount: 1_000_000,
+            deposit: 1_000_000,
+            bump: escrow_bump,
+            vault_bump,
+            ..Default::default()
+        };
+
+        let result = mollusk.process_and_validate_instruction(
             &instruction,
-            &vec![
+            &[
                 (maker, maker_account),
                 (mint_x, mint_x_account),
                 (mint_y, mint_y_account),
@@ -160,15 +384,122 @@ mod escrow_tests{
                 (escrow, escrow_account),
                 (system_program, system_account),
                 (token_program, token_account),
+                config_account(&mollusk, 0, 0, system_program),
             ],
             &[
                 Check::success(),
+                Check::account(&escrow).owner(&ID).data(&escrow_account_data(&expected)).build(),
+                Check::account(&vault).owner(&token_program).build(),
+            ],
+        );
+
+        assert_eq!(client::decode_escrow(result.get_account(&escrow).unwrap().data()), Some(expected));
+
+
+    }
+
+    #[test]
+    fn test_malformed_instruction_data() {
+        let (mollusk, (system_program, system_account), (token_program, token_program_account)) = setup();
+
+        let maker = Pubkey::new_from_array([0x02; 32]);
+        let taker = Pubkey::new_from_array([0x08; 32]);
+        let mint_x = Pubkey::new_from_array([0x03; 32]);
+        let mint_y = Pubkey::new_from_array([0x04; 32]);
+
+        let seed = 42u64;
+        let (escrow, escrow_bump) = client::escrow_address(&maker, seed);
+        let (vault, _) = client::vault_address(&escrow, &mint_x);
+
+        // Make data cut off in the middle of the amount.
+        let make = Instruction::new_with_bytes(
+            ID,
+            &[vec![0], vec![escrow_bump], seed.to_le_bytes().to_vec(), vec![0x40, 0x42]].concat(),
+            vec![
+                AccountMeta::new(maker, true),
+                AccountMeta::new_readonly(mint_x, false),
+                AccountMeta::new_readonly(mint_y, false),
+                AccountMeta::new(maker, false),
+                AccountMeta::new(vault, false),
+                AccountMeta::new(escrow, false),
+                AccountMeta::new_readonly(system_program, false),
+                AccountMeta::new_readonly(token_program, false),
+                AccountMeta::new_readonly(client::config_address().0, false),
             ],
         );
 
+        let accounts = [
+            (maker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (taker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (mint_x, mint_account(&mollusk)),
+            (mint_y, mint_account(&mollusk)),
+            (vault, AccountSharedData::new(0, 0, &system_program)),
+            (escrow, AccountSharedData::new(0, 0, &system_program)),
+            (system_program, system_account),
+            (token_program, token_program_account),
+            config_account(&mollusk, 0, 0, system_program),
+        ];
+
+        mollusk.process_and_validate_instruction(&make, &accounts, &[Check::err(ProgramError::InvalidInstructionData)]);
 
+        // Take data shorter than the u64 fill.
+        let mut take = take_instruction(taker, maker, seed, mint_x, mint_y, taker, taker, maker, 0);
+        take.data.truncate(4);
+
+        mollusk.process_and_validate_instruction(&take, &accounts, &[Check::err(ProgramError::InvalidInstructionData)]);
+
+        // No discriminator at all.
+        let empty = Instruction::new_with_bytes(ID, &[], vec![]);
+
+        mollusk.process_and_validate_instruction(&empty, &[], &[Check::err(ProgramError::InvalidInstructionData)]);
+    }
+
+    // Builds a take instruction paying `fill` of mint_y into the escrow of `maker` for `seed`,
+    // with both mints owned by the SPL token program.
+    #[allow(clippy::too_many_arguments)]
+    fn take_instruction(
+        taker: Pubkey,
+        maker: Pubkey,
+        seed: u64,
+        mint_x: Pubkey,
+        mint_y: Pubkey,
+        taker_ata_x: Pubkey,
+        taker_ata_y: Pubkey,
+        maker_ata_y: Pubkey,
+        fill: u64,
+    ) -> Instruction {
+        client::take_ix(&TakeParams {
+            taker,
+            maker,
+            seed,
+            mint_x,
+            mint_y,
+            taker_ata_x,
+            taker_ata_y,
+            maker_ata_y,
+            token_program_x: spl_token::ID,
+            token_program_y: spl_token::ID,
+            fill,
+            version: 0,
+            treasury: solana_sdk::system_program::ID,
+            referrer: None,
+            referrer_bps: 0,
+            multisig: false,
+        })
     }
 
+    // Builds a refund instruction for the escrow of `maker` for `seed`.
+    fn refund_instruction(maker: Pubkey, seed: u64, mint_x: Pubkey, maker_ata_x: Pubkey) -> Instruction {
+        client::refund_ix(&RefundParams {
+            maker,
+            seed,
+            mint_x,
+            maker_ata_x,
+            token_program: spl_token::ID,
+            multisig: false,
+            auction: None,
+        })
+    }
 
     #[test]
     fn test_take() {
@@ -197,8 +528,8 @@ mod escrow_tests{
         let taker = Pubkey::new_from_array([0x08; 32]);
 
         // Create maker and taker accounts.
-        let maker_account = AccountSharedData::new(1 * LAMPORTS_PER_SOL, 0, &system_program);
-        let taker_account = AccountSharedData::new(1 * LAMPORTS_PER_SOL, 0, &system_program);
+        let maker_account = AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program);
+        let taker_account = AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program);
 
         // Define mint keys.
         let mint_x = Pubkey::new_from_array([0x03; 32]);
@@ -308,10 +639,12 @@ mod escrow_tests{
         )
         .unwrap();
 
-        // Derive the escrow PDA and bump using seeds ["escrow", maker.as_ref()].
-        let (escrow, escrow_bump) = Pubkey::find_program_address(&[b"escrow", maker.as_ref()], &ID);
+        // Derive the escrow PDA and bump using seeds ["escrow", maker, seed].
+        let seed = 7u64;
+        let (escrow, escrow_bump) = Pubkey::find_program_address(&[b"escrow", maker.as_ref(), &seed.to_le_bytes()], &ID);
 
-        let vault = Pubkey::new_from_array([0x06; 32]);
+        // Derive the vault PDA and bump using seeds ["vault", escrow, mint_x].
+        let (vault, vault_bump) = Pubkey::find_program_address(&[b"vault", escrow.as_ref(), mint_x.as_ref()], &ID);
         // Create the vault account with the escrow PDA as its owner.
         let mut vault_account = AccountSharedData::new(
             mollusk.sysvars.rent.minimum_balance(spl_token::state::Account::LEN),
@@ -333,35 +666,48 @@ mod escrow_tests{
         )
         .unwrap();
 
-        // Create the escrow account with enough space (assume Escrow::LEN is 105 bytes).
+        // Create the escrow account owned by the program with Escrow::LEN bytes.
         let mut escrow_account = AccountSharedData::new(
-            mollusk.sysvars.rent.minimum_balance(105),
-            105,
-            &system_program,
+            mollusk.sysvars.rent.minimum_balance(Escrow::LEN),
+            Escrow::LEN,
+            &ID,
         );
         // Write the escrow state as a concatenated vector.
         let escrow_state_data = [
             maker.as_ref().to_vec(),
             mint_x.as_ref().to_vec(),
             mint_y.as_ref().to_vec(),
+            [0; 32 * 3].to_vec(), // escrow.taker, escrow.collection, escrow.arbiter
+            seed.to_le_bytes().to_vec(), // escrow.seed
             1_000_000u64.to_le_bytes().to_vec(), // escrow.amount
-            vec![escrow_bump],
+            2_000_000u64.to_le_bytes().to_vec(), // escrow.deposit
+            [0; 8 * 2].to_vec(), // escrow.expiry, escrow.version
+            vec![escrow_bump, vault_bump],
+            [0; 2 + 4].to_vec(), // escrow.flags, escrow.status and padding
         ]
         .concat();
         escrow_account
             .data_as_mut_slice()
             .copy_from_slice(&escrow_state_data);
 
-        // Construct the "take" instruction.
+        let (config, config_account) = config_account(&mollusk, 0, 0, system_program);
+
+        // Construct the "take" instruction filling the whole 1,000,000 requested.
         // Expected accounts order:
-        // taker, maker, mint_x, mint_y, taker_ata_x, taker_ata_y, maker_ata_y, vault, escrow, token_program, system_program
-        let instruction = Instruction::new_with_bytes(
+        // taker, maker, mint_x, mint_y, taker_ata_x, taker_ata_y, maker_ata_y, vault, escrow, token_program_x, token_program_y, system_program, config, treasury
+        let take = |maker_ata_y| Instruction::new_with_bytes(
             ID,
-            &[], // No extra instruction data needed.
+            &[
+                vec![1],
+                1_000_000u64.to_le_bytes().to_vec(), // fill
+                0u64.to_le_bytes().to_vec(), // version
+                0u16.to_le_bytes().to_vec(), // referrer_bps
+            ]
+            .concat(),
             vec![
                 AccountMeta::new(taker, true),
                 AccountMeta::new(maker, false),
-                AccountMeta::new_readonly(mint_x, false),
+                AccountMeta::new(mint_x, false),
                 AccountMeta::new_readonly(mint_y, false),
                 AccountMeta::new(taker_ata_x, false),
                 AccountMeta::new(taker_ata_y, false),
@@ -369,30 +715,49 @@ mod escrow_tests{
                 AccountMeta::new(vault, false),
                 AccountMeta::new(escrow, true),
                 AccountMeta::new_readonly(token_program, false),
+                AccountMeta::new_readonly(token_program, false),
                 AccountMeta::new_readonly(system_program, false),
+                AccountMeta::new_readonly(config, false),
+                AccountMeta::new(system_program, false),
             ],
         );
 
-        // Execute and validate the "take" instruction.
+        let accounts = [
+            (taker, taker_account),
+            (maker, maker_account),
+            (mint_x, mint_x_account),
+            (mint_y, mint_y_account),
+            (taker_ata_x, taker_ata_x_account),
+            (taker_ata_y, taker_ata_y_account),
+            (maker_ata_y, maker_ata_y_account),
+            (vault, vault_account),
+            (escrow, escrow_account),
+            (system_program, system_account),
+            (token_program, token_account),
+            (config, config_account),
+        ];
+
+        // The payment can't be routed to an account the maker doesn't own.
         mollusk.process_and_validate_instruction(
-            &instruction,
-            &vec![
-                (taker, taker_account),
-                (maker, maker_account),
-                (mint_x, mint_x_account),
-                (mint_y, mint_y_account),
-                (taker_ata_x, taker_ata_x_account),
-                (taker_ata_y, taker_ata_y_account),
-                (maker_ata_y, maker_ata_y_account),
-                (vault, vault_account),
-                (escrow, escrow_account),
-                (system_program, system_account),
-                (token_program, token_account),
+            &take(taker_ata_y),
+            &accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::InvalidTokenAccount as u32))],
+        );
+
+        // Execute and validate the "take" instruction.
+        let result = mollusk.process_and_validate_instruction(
+            &take(maker_ata_y),
+            &accounts,
+            &[
+                Check::success(),
+                Check::account(&vault).lamports(0).build(),
+                Check::account(&escrow).lamports(0).build(),
             ],
-            &[Check::success()],
         );
-    }
 
+        assert_eq!(token_amount(result.get_account(&taker_ata_x).unwrap()), 2_000_000);
+        assert_eq!(token_amount(result.get_account(&maker_ata_y).unwrap()), 1_000_000);
+    }
 
     #[test]
     fn test_refund() {
@@ -420,7 +785,7 @@ mod escrow_tests{
         let maker = Pubkey::new_from_array([0x02; 32]);
 
         // Create maker account with some lamports.
-        let maker_account = AccountSharedData::new(1 * LAMPORTS_PER_SOL, 0, &system_program);
+        let maker_account = AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program);
 
         // Define mint keys.
         let mint_x = Pubkey::new_from_array([0x03; 32]);
@@ -444,24 +809,6 @@ mod escrow_tests{
         )
         .unwrap();
 
-        // Create and initialize mint_y account.
-        let mut mint_y_account = AccountSharedData::new(
-            mollusk.sysvars.rent.minimum_balance(spl_token::state::Mint::LEN),
-            spl_token::state::Mint::LEN,
-            &token_program,
-        );
-        solana_sdk::program_pack::Pack::pack(
-            spl_token::state::Mint {
-                mint_authority: COption::None,
-                supply: 100_000_000,
-                decimals: 6,
-                is_initialized: true,
-                freeze_authority: COption::None,
-            },
-            mint_y_account.data_as_mut_slice(),
-        )
-        .unwrap();
-
         // Define maker's associated token account for token X (destination for refund).
         let maker_ata_x = Pubkey::new_from_array([0x05; 32]);
         let mut maker_ata_x_account = AccountSharedData::new(
@@ -485,11 +832,13 @@ mod escrow_tests{
         )
         .unwrap();
 
-        // Derive the escrow PDA and bump using seeds ["escrow", maker.as_ref()].
-        let (escrow, escrow_bump) = Pubkey::find_program_address(&[b"escrow", maker.as_ref()], &ID);
+        // Derive the escrow PDA and bump using seeds ["escrow", maker, seed].
+        let seed = 7u64;
+        let (escrow, escrow_bump) = Pubkey::find_program_address(&[b"escrow", maker.as_ref(), &seed.to_le_bytes()], &ID);
 
+        // Derive the vault PDA and bump using seeds ["vault", escrow, mint_x].
+        let (vault, vault_bump) = Pubkey::find_program_address(&[b"vault", escrow.as_ref(), mint_x.as_ref()], &ID);
         // Create the vault account with the escrow PDA as its owner.
-        let vault = Pubkey::new_from_array([0x06; 32]);
         let mut vault_account = AccountSharedData::new(
             mollusk.sysvars.rent.minimum_balance(spl_token::state::Account::LEN),
             spl_token::state::Account::LEN,
@@ -511,19 +860,24 @@ mod escrow_tests{
         )
         .unwrap();
 
-        // Create the escrow account with enough space (assume Escrow::LEN is 105 bytes).
+        // Create the escrow account owned by the program with Escrow::LEN bytes.
         let mut escrow_account = AccountSharedData::new(
-            mollusk.sysvars.rent.minimum_balance(105),
-            105,
-            &system_program,
+            mollusk.sysvars.rent.minimum_balance(Escrow::LEN),
+            Escrow::LEN,
+            &ID,
         );
-        // Build escrow state data as a concatenated vector (maker, mint_x, mint_y, amount, bump).
+        // Build escrow state data as a concatenated vector (maker, mint_x, mint_y, seed, amount, deposit, bumps).
         let escrow_state_data = [
             maker.as_ref().to_vec(),
             mint_x.as_ref().to_vec(),
             mint_y.as_ref().to_vec(),
+            [0; 32 * 3].to_vec(), // escrow.taker, escrow.collection, escrow.arbiter
+            seed.to_le_bytes().to_vec(), // escrow.seed
             1_000_000u64.to_le_bytes().to_vec(), // escrow.amount
-            vec![escrow_bump],
+            2_000_000u64.to_le_bytes().to_vec(), // escrow.deposit
+            [0; 8 * 2].to_vec(), // escrow.expiry, escrow.version
+            vec![escrow_bump, vault_bump],
+            [0; 2 + 4].to_vec(), // escrow.flags, escrow.status and padding
         ]
         .concat();
         escrow_account
@@ -535,10 +889,10 @@ mod escrow_tests{
         // maker, mint_x, maker_ata_x, vault, escrow, token_program, system_program
         let instruction = Instruction::new_with_bytes(
             ID,
-            &[], // No extra instruction data is required.
+            &[2], // No data after the discriminator.
             vec![
                 AccountMeta::new(maker, true),
-                AccountMeta::new_readonly(mint_x, false),
+                AccountMeta::new(mint_x, false),
                 AccountMeta::new(maker_ata_x, false),
                 AccountMeta::new(vault, false),
                 AccountMeta::new(escrow, true),
@@ -547,22 +901,2695 @@ mod escrow_tests{
             ],
         );
 
-        // Execute and validate the "refund" instruction.
+        let accounts = [
+            (maker, maker_account),
+            (mint_x, mint_x_account),
+            (maker_ata_x, maker_ata_x_account),
+            (vault, vault_account),
+            (escrow, escrow_account),
+            (system_program, system_account),
+            (token_program, token_account),
+        ];
+
+        // Refund takes no arguments, so trailing data is rejected.
+        let mut trailing = instruction.clone();
+        trailing.data.push(0);
         mollusk.process_and_validate_instruction(
+            &trailing,
+            &accounts,
+            &[Check::err(ProgramError::InvalidInstructionData)],
+        );
+
+        // Execute and validate the "refund" instruction.
+        let result = mollusk.process_and_validate_instruction(
             &instruction,
-            &vec![
-                (maker, maker_account),
-                (mint_x, mint_x_account),
-                (maker_ata_x, maker_ata_x_account),
-                (vault, vault_account),
-                (escrow, escrow_account),
+            &accounts,
+            &[
+                Check::success(),
+                Check::account(&vault).lamports(0).build(),
+                Check::account(&escrow).lamports(0).build(),
+            ],
+        );
+
+        assert_eq!(token_amount(result.get_account(&maker_ata_x).unwrap()), 2_000_000);
+    }
+
+    #[test]
+    fn test_concurrent_escrows() {
+        let (mollusk, (system_program, system_account), (token_program, token_program_account)) = setup();
+
+        let maker = Pubkey::new_from_array([0x02; 32]);
+        let taker = Pubkey::new_from_array([0x08; 32]);
+
+        let mint_x = Pubkey::new_from_array([0x03; 32]);
+        let mint_y = Pubkey::new_from_array([0x04; 32]);
+
+        let maker_ata_x = Pubkey::new_from_array([0x05; 32]);
+        let maker_ata_y = Pubkey::new_from_array([0x0B; 32]);
+        let taker_ata_x = Pubkey::new_from_array([0x09; 32]);
+        let taker_ata_y = Pubkey::new_from_array([0x0A; 32]);
+
+        // Two open offers from the same maker, told apart only by their seed.
+        let (escrow_a, bump_a) = client::escrow_address(&maker, 1);
+        let (escrow_b, bump_b) = client::escrow_address(&maker, 2);
+        assert_ne!(escrow_a, escrow_b);
+
+        let (vault_a, vault_bump_a) = client::vault_address(&escrow_a, &mint_x);
+        let (vault_b, vault_bump_b) = client::vault_address(&escrow_b, &mint_x);
+
+        let state_a = Escrow {
+            maker: maker.to_bytes(),
+            mint_x: mint_x.to_bytes(),
+            mint_y: mint_y.to_bytes(),
+            seed: 1,
+            amount: 1_000_000,
+            deposit: 2_000_000,
+            bump: bump_a,
+            vault_bump: vault_bump_a,
+            ..Default::default()
+        };
+        let state_b = Escrow {
+            maker: maker.to_bytes(),
+            mint_x: mint_x.to_bytes(),
+            mint_y: mint_y.to_bytes(),
+            seed: 2,
+            amount: 3_000_000,
+            deposit: 4_000_000,
+            bump: bump_b,
+            vault_bump: vault_bump_b,
+            ..Default::default()
+        };
+
+        // Take the first offer and refund the second one, leaving each other untouched.
+        let result = mollusk.process_and_validate_instruction_chain(
+            &[
+                take_instruction(taker, maker, 1, mint_x, mint_y, taker_ata_x, taker_ata_y, maker_ata_y, 1_000_000),
+                refund_instruction(maker, 2, mint_x, maker_ata_x),
+            ],
+            &[
+                (taker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+                (maker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+                (mint_x, mint_account(&mollusk)),
+                (mint_y, mint_account(&mollusk)),
+                (maker_ata_x, token_account(&mollusk, mint_x, maker, 0)),
+                (maker_ata_y, token_account(&mollusk, mint_y, maker, 0)),
+                (taker_ata_x, token_account(&mollusk, mint_x, taker, 0)),
+                (taker_ata_y, token_account(&mollusk, mint_y, taker, 5_000_000)),
+                (vault_a, token_account(&mollusk, mint_x, escrow_a, 2_000_000)),
+                (vault_b, token_account(&mollusk, mint_x, escrow_b, 4_000_000)),
+                (escrow_a, escrow_account(&mollusk, &state_a)),
+                (escrow_b, escrow_account(&mollusk, &state_b)),
                 (system_program, system_account),
-                (token_program, token_account),
+                (token_program, token_program_account),
+                config_account(&mollusk, 0, 0, system_program),
+            ],
+            &[
+                Check::success(),
+                Check::account(&escrow_a).lamports(0).build(),
+                Check::account(&escrow_b).lamports(0).build(),
             ],
-            &[Check::success()],
         );
+
+        assert_eq!(token_amount(result.get_account(&taker_ata_x).unwrap()), 2_000_000);
+        assert_eq!(token_amount(result.get_account(&taker_ata_y).unwrap()), 4_000_000);
+        assert_eq!(token_amount(result.get_account(&maker_ata_y).unwrap()), 1_000_000);
+        assert_eq!(token_amount(result.get_account(&maker_ata_x).unwrap()), 4_000_000);
     }
 
+    #[test]
+    fn test_take_partial_fills() {
+        let (mollusk, (system_program, system_account), (token_program, token_program_account)) = setup();
+
+        let maker = Pubkey::new_from_array([0x02; 32]);
+        let taker_a = Pubkey::new_from_array([0x08; 32]);
+        let taker_b = Pubkey::new_from_array([0x0C; 32]);
+
+        let mint_x = Pubkey::new_from_array([0x03; 32]);
+        let mint_y = Pubkey::new_from_array([0x04; 32]);
+
+        let maker_ata_y = Pubkey::new_from_array([0x0B; 32]);
+        let taker_a_ata_x = Pubkey::new_from_array([0x09; 32]);
+        let taker_a_ata_y = Pubkey::new_from_array([0x0A; 32]);
+        let taker_b_ata_x = Pubkey::new_from_array([0x0D; 32]);
+        let taker_b_ata_y = Pubkey::new_from_array([0x0E; 32]);
+
+        let seed = 7u64;
+        let (escrow, escrow_bump) = client::escrow_address(&maker, seed);
+        let (vault, vault_bump) = client::vault_address(&escrow, &mint_x);
+
+        // 2,000,000 of mint_x offered for 1,000,000 of mint_y.
+        let escrow_state = Escrow {
+            maker: maker.to_bytes(),
+            mint_x: mint_x.to_bytes(),
+            mint_y: mint_y.to_bytes(),
+            seed,
+            amount: 1_000_000,
+            deposit: 2_000_000,
+            bump: escrow_bump,
+            vault_bump,
+            ..Default::default()
+        };
+
+        let accounts = [
+            (taker_a, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (taker_b, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (maker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (mint_x, mint_account(&mollusk)),
+            (mint_y, mint_account(&mollusk)),
+            (maker_ata_y, token_account(&mollusk, mint_y, maker, 0)),
+            (taker_a_ata_x, token_account(&mollusk, mint_x, taker_a, 0)),
+            (taker_a_ata_y, token_account(&mollusk, mint_y, taker_a, 1_000_000)),
+            (taker_b_ata_x, token_account(&mollusk, mint_x, taker_b, 0)),
+            (taker_b_ata_y, token_account(&mollusk, mint_y, taker_b, 1_000_000)),
+            (vault, token_account(&mollusk, mint_x, escrow, 2_000_000)),
+            (escrow, escrow_account(&mollusk, &escrow_state)),
+            (system_program, system_account),
+            (token_program, token_program_account),
+            config_account(&mollusk, 0, 0, system_program),
+        ];
+
+        // A quarter fill leaves the offer open with the remaining terms.
+        let first_fill = take_instruction(taker_a, maker, seed, mint_x, mint_y, taker_a_ata_x, taker_a_ata_y, maker_ata_y, 250_000);
+        let remaining = escrow_account_data(&Escrow {
+            amount: 750_000,
+            deposit: 1_500_000,
+            ..escrow_state
+        });
+
+        let result = mollusk.process_and_validate_instruction(
+            &first_fill,
+            &accounts,
+            &[
+                Check::success(),
+                Check::account(&escrow).data(&remaining).build(),
+            ],
+        );
+
+        assert_eq!(token_amount(result.get_account(&taker_a_ata_x).unwrap()), 500_000);
+        assert_eq!(token_amount(result.get_account(&vault).unwrap()), 1_500_000);
+
+        // Filling more than what is left is rejected.
+        mollusk.process_and_validate_instruction(
+            &take_instruction(taker_b, maker, seed, mint_x, mint_y, taker_b_ata_x, taker_b_ata_y, maker_ata_y, 750_001),
+            &result.resulting_accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::InvalidFillAmount as u32))],
+        );
 
+        // A second taker fills the rest, which closes the vault and escrow.
+        let result = mollusk.process_and_validate_instruction_chain(
+            &[
+                first_fill,
+                take_instruction(taker_b, maker, seed, mint_x, mint_y, taker_b_ata_x, taker_b_ata_y, maker_ata_y, 750_000),
+            ],
+            &accounts,
+            &[
+                Check::success(),
+                Check::account(&vault).lamports(0).build(),
+                Check::account(&escrow).lamports(0).build(),
+            ],
+        );
+
+        assert_eq!(token_amount(result.get_account(&taker_b_ata_x).unwrap()), 1_500_000);
+        assert_eq!(token_amount(result.get_account(&maker_ata_y).unwrap()), 1_000_000);
+    }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_expiry() {
+        let (mut mollusk, (system_program, system_account), (token_program, token_program_account)) = setup();
+
+        let maker = Pubkey::new_from_array([0x02; 32]);
+        let taker = Pubkey::new_from_array([0x08; 32]);
+
+        let mint_x = Pubkey::new_from_array([0x03; 32]);
+        let mint_y = Pubkey::new_from_array([0x04; 32]);
+
+        let maker_ata_x = Pubkey::new_from_array([0x05; 32]);
+        let maker_ata_y = Pubkey::new_from_array([0x0B; 32]);
+        let taker_ata_x = Pubkey::new_from_array([0x09; 32]);
+        let taker_ata_y = Pubkey::new_from_array([0x0A; 32]);
+
+        let seed = 7u64;
+        let (escrow, escrow_bump) = client::escrow_address(&maker, seed);
+        let (vault, vault_bump) = client::vault_address(&escrow, &mint_x);
+
+        let escrow_state = Escrow {
+            maker: maker.to_bytes(),
+            mint_x: mint_x.to_bytes(),
+            mint_y: mint_y.to_bytes(),
+            seed,
+            amount: 1_000_000,
+            deposit: 2_000_000,
+            expiry: 1_000,
+            bump: escrow_bump,
+            vault_bump,
+            ..Default::default()
+        };
+
+        let accounts = [
+            (taker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (maker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (mint_x, mint_account(&mollusk)),
+            (mint_y, mint_account(&mollusk)),
+            (maker_ata_x, token_account(&mollusk, mint_x, maker, 0)),
+            (maker_ata_y, token_account(&mollusk, mint_y, maker, 0)),
+            (taker_ata_x, token_account(&mollusk, mint_x, taker, 0)),
+            (taker_ata_y, token_account(&mollusk, mint_y, taker, 1_000_000)),
+            (vault, token_account(&mollusk, mint_x, escrow, 2_000_000)),
+            (escrow, escrow_account(&mollusk, &escrow_state)),
+            (system_program, system_account),
+            (token_program, token_program_account),
+            config_account(&mollusk, 0, 0, system_program),
+        ];
+
+        let take = take_instruction(taker, maker, seed, mint_x, mint_y, taker_ata_x, taker_ata_y, maker_ata_y, 1_000_000);
+
+        // Still open one second before expiry.
+        mollusk.sysvars.clock.unix_timestamp = 999;
+        mollusk.process_and_validate_instruction(&take, &accounts, &[Check::success()]);
+
+        // Rejected once the expiry is reached, but the maker can still get the deposit back.
+        mollusk.sysvars.clock.unix_timestamp = 1_000;
+        mollusk.process_and_validate_instruction(
+            &take,
+            &accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::EscrowExpired as u32))],
+        );
+
+        let result = mollusk.process_and_validate_instruction(
+            &refund_instruction(maker, seed, mint_x, maker_ata_x),
+            &accounts,
+            &[
+                Check::success(),
+                Check::account(&escrow).lamports(0).build(),
+            ],
+        );
+
+        assert_eq!(token_amount(result.get_account(&maker_ata_x).unwrap()), 2_000_000);
+    }
+
+    #[test]
+    fn test_private_escrow() {
+        let (mollusk, (system_program, system_account), (token_program, token_program_account)) = setup();
+
+        let maker = Pubkey::new_from_array([0x02; 32]);
+        let taker = Pubkey::new_from_array([0x08; 32]);
+        let outsider = Pubkey::new_from_array([0x0C; 32]);
+
+        let mint_x = Pubkey::new_from_array([0x03; 32]);
+        let mint_y = Pubkey::new_from_array([0x04; 32]);
+
+        let maker_ata_y = Pubkey::new_from_array([0x0B; 32]);
+        let taker_ata_x = Pubkey::new_from_array([0x09; 32]);
+        let taker_ata_y = Pubkey::new_from_array([0x0A; 32]);
+        let outsider_ata_x = Pubkey::new_from_array([0x0D; 32]);
+        let outsider_ata_y = Pubkey::new_from_array([0x0E; 32]);
+
+        let seed = 7u64;
+        let (escrow, escrow_bump) = client::escrow_address(&maker, seed);
+        let (vault, vault_bump) = client::vault_address(&escrow, &mint_x);
+
+        // Only `taker` may fill this offer.
+        let escrow_state = Escrow {
+            maker: maker.to_bytes(),
+            mint_x: mint_x.to_bytes(),
+            mint_y: mint_y.to_bytes(),
+            taker: taker.to_bytes(),
+            seed,
+            amount: 1_000_000,
+            deposit: 2_000_000,
+            bump: escrow_bump,
+            vault_bump,
+            ..Default::default()
+        };
+
+        let accounts = [
+            (taker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (outsider, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (maker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (mint_x, mint_account(&mollusk)),
+            (mint_y, mint_account(&mollusk)),
+            (maker_ata_y, token_account(&mollusk, mint_y, maker, 0)),
+            (taker_ata_x, token_account(&mollusk, mint_x, taker, 0)),
+            (taker_ata_y, token_account(&mollusk, mint_y, taker, 1_000_000)),
+            (outsider_ata_x, token_account(&mollusk, mint_x, outsider, 0)),
+            (outsider_ata_y, token_account(&mollusk, mint_y, outsider, 1_000_000)),
+            (vault, token_account(&mollusk, mint_x, escrow, 2_000_000)),
+            (escrow, escrow_account(&mollusk, &escrow_state)),
+            (system_program, system_account),
+            (token_program, token_program_account),
+            config_account(&mollusk, 0, 0, system_program),
+        ];
+
+        mollusk.process_and_validate_instruction(
+            &take_instruction(outsider, maker, seed, mint_x, mint_y, outsider_ata_x, outsider_ata_y, maker_ata_y, 1_000_000),
+            &accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::UnauthorizedTaker as u32))],
+        );
+
+        let result = mollusk.process_and_validate_instruction(
+            &take_instruction(taker, maker, seed, mint_x, mint_y, taker_ata_x, taker_ata_y, maker_ata_y, 1_000_000),
+            &accounts,
+            &[
+                Check::success(),
+                Check::account(&escrow).lamports(0).build(),
+            ],
+        );
+
+        assert_eq!(token_amount(result.get_account(&taker_ata_x).unwrap()), 2_000_000);
+    }
+
+    #[test]
+    fn test_update() {
+        let (mollusk, (system_program, system_account), (token_program, token_program_account)) = setup();
+
+        let maker = Pubkey::new_from_array([0x02; 32]);
+        let taker = Pubkey::new_from_array([0x08; 32]);
+
+        let mint_x = Pubkey::new_from_array([0x03; 32]);
+        let mint_y = Pubkey::new_from_array([0x04; 32]);
+
+        let taker_ata_x = Pubkey::new_from_array([0x09; 32]);
+        let taker_ata_y = Pubkey::new_from_array([0x0A; 32]);
+        let maker_ata_y = Pubkey::new_from_array([0x0B; 32]);
+
+        let seed = 7u64;
+        let (escrow, escrow_bump) = client::escrow_address(&maker, seed);
+        let (vault, vault_bump) = client::vault_address(&escrow, &mint_x);
+
+        let escrow_state = Escrow {
+            maker: maker.to_bytes(),
+            mint_x: mint_x.to_bytes(),
+            mint_y: mint_y.to_bytes(),
+            seed,
+            amount: 1_000_000,
+            deposit: 2_000_000,
+            bump: escrow_bump,
+            vault_bump,
+            ..Default::default()
+        };
+
+        let accounts = [
+            (taker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (maker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (mint_x, mint_account(&mollusk)),
+            (mint_y, mint_account(&mollusk)),
+            (taker_ata_x, token_account(&mollusk, mint_x, taker, 0)),
+            (taker_ata_y, token_account(&mollusk, mint_y, taker, 2_000_000)),
+            (maker_ata_y, token_account(&mollusk, mint_y, maker, 0)),
+            (vault, token_account(&mollusk, mint_x, escrow, 2_000_000)),
+            (escrow, escrow_account(&mollusk, &escrow_state)),
+            (system_program, system_account),
+            (token_program, token_program_account),
+            config_account(&mollusk, 0, 0, system_program),
+        ];
+
+        let update = client::update_ix(&UpdateParams {
+            maker,
+            seed,
+            amount: 2_000_000,
+            expiry: 0,
+            taker: None,
+        });
+
+        // Only the maker can change the terms.
+        let mut forged = update.clone();
+        forged.accounts[0].pubkey = taker;
+        mollusk.process_and_validate_instruction(
+            &forged,
+            &accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::InvalidEscrowAddress as u32))],
+        );
+
+        // The maker doubles the price in place, which bumps the version.
+        let updated = escrow_account_data(&Escrow {
+            amount: 2_000_000,
+            version: 1,
+            ..escrow_state
+        });
+        mollusk.process_and_validate_instruction(
+            &update,
+            &accounts,
+            &[
+                Check::success(),
+                Check::account(&escrow).data(&updated).build(),
+            ],
+        );
+
+        let take = |fill, version| client::take_ix(&TakeParams {
+            taker,
+            maker,
+            seed,
+            mint_x,
+            mint_y,
+            taker_ata_x,
+            taker_ata_y,
+            maker_ata_y,
+            token_program_x: token_program,
+            token_program_y: token_program,
+            fill,
+            version,
+            treasury: system_program,
+            referrer: None,
+            referrer_bps: 0,
+            multisig: false,
+        });
+
+        // A take built against the original terms is rejected once they changed.
+        mollusk.process_and_validate_instruction_chain(
+            &[update.clone(), take(1_000_000, 0)],
+            &accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::TermsChanged as u32))],
+        );
+
+        // A take agreeing to the new terms pays the new amount for the whole deposit.
+        let result = mollusk.process_and_validate_instruction_chain(
+            &[update, take(2_000_000, 1)],
+            &accounts,
+            &[
+                Check::success(),
+                Check::account(&escrow).lamports(0).build(),
+            ],
+        );
+
+        assert_eq!(token_amount(result.get_account(&taker_ata_x).unwrap()), 2_000_000);
+        assert_eq!(token_amount(result.get_account(&maker_ata_y).unwrap()), 2_000_000);
+    }
+
+    #[test]
+    fn test_deposit_and_withdraw() {
+        let (mollusk, (system_program, system_account), (token_program, token_program_account)) = setup();
+
+        let maker = Pubkey::new_from_array([0x02; 32]);
+
+        let mint_x = Pubkey::new_from_array([0x03; 32]);
+        let mint_y = Pubkey::new_from_array([0x04; 32]);
+
+        let maker_ata_x = Pubkey::new_from_array([0x05; 32]);
+
+        let seed = 7u64;
+        let (escrow, escrow_bump) = client::escrow_address(&maker, seed);
+        let (vault, vault_bump) = client::vault_address(&escrow, &mint_x);
+
+        // 2,000,000 of mint_x offered for 1,000,000 of mint_y.
+        let escrow_state = Escrow {
+            maker: maker.to_bytes(),
+            mint_x: mint_x.to_bytes(),
+            mint_y: mint_y.to_bytes(),
+            seed,
+            amount: 1_000_000,
+            deposit: 2_000_000,
+            bump: escrow_bump,
+            vault_bump,
+            ..Default::default()
+        };
+
+        let accounts = [
+            (maker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (mint_x, mint_account(&mollusk)),
+            (maker_ata_x, token_account(&mollusk, mint_x, maker, 1_000_000)),
+            (vault, token_account(&mollusk, mint_x, escrow, 2_000_000)),
+            (escrow, escrow_account(&mollusk, &escrow_state)),
+            (system_program, system_account),
+            (token_program, token_program_account),
+        ];
+
+        let resize = |amount| ResizeParams {
+            maker,
+            seed,
+            mint_x,
+            maker_ata_x,
+            token_program,
+            amount,
+        };
+
+        // Topping up by half raises the requested amount by half, keeping the price.
+        let topped_up = escrow_account_data(&Escrow {
+            amount: 1_500_000,
+            deposit: 3_000_000,
+            version: 1,
+            ..escrow_state
+        });
+        let result = mollusk.process_and_validate_instruction(
+            &client::deposit_ix(&resize(1_000_000)),
+            &accounts,
+            &[
+                Check::success(),
+                Check::account(&escrow).data(&topped_up).build(),
+            ],
+        );
+
+        assert_eq!(token_amount(result.get_account(&vault).unwrap()), 3_000_000);
+
+        // Withdrawing two thirds of it lowers the requested amount accordingly.
+        let withdrawn = escrow_account_data(&Escrow {
+            amount: 500_000,
+            deposit: 1_000_000,
+            version: 2,
+            ..escrow_state
+        });
+        let result = mollusk.process_and_validate_instruction(
+            &client::withdraw_ix(&resize(2_000_000)),
+            &result.resulting_accounts,
+            &[
+                Check::success(),
+                Check::account(&escrow).data(&withdrawn).build(),
+            ],
+        );
+
+        assert_eq!(token_amount(result.get_account(&maker_ata_x).unwrap()), 2_000_000);
+
+        // Emptying the vault is left to Refund.
+        mollusk.process_and_validate_instruction(
+            &client::withdraw_ix(&resize(1_000_000)),
+            &result.resulting_accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::InsufficientDeposit as u32))],
+        );
+    }
+
+    #[test]
+    fn test_basket() {
+        let (mollusk, (system_program, system_account), (token_program, token_program_account)) = setup();
+
+        let maker = Pubkey::new_from_array([0x02; 32]);
+        let taker = Pubkey::new_from_array([0x08; 32]);
+
+        // Two mints offered for two others.
+        let mint_x1 = Pubkey::new_from_array([0x03; 32]);
+        let mint_x2 = Pubkey::new_from_array([0x13; 32]);
+        let mint_y1 = Pubkey::new_from_array([0x04; 32]);
+        let mint_y2 = Pubkey::new_from_array([0x14; 32]);
+
+        let maker_ata_x1 = Pubkey::new_from_array([0x05; 32]);
+        let maker_ata_x2 = Pubkey::new_from_array([0x15; 32]);
+        let maker_ata_y1 = Pubkey::new_from_array([0x0B; 32]);
+        let maker_ata_y2 = Pubkey::new_from_array([0x1B; 32]);
+        let taker_ata_x1 = Pubkey::new_from_array([0x09; 32]);
+        let taker_ata_x2 = Pubkey::new_from_array([0x19; 32]);
+        let taker_ata_y1 = Pubkey::new_from_array([0x0A; 32]);
+        let taker_ata_y2 = Pubkey::new_from_array([0x1A; 32]);
+
+        let treasury = Pubkey::new_from_array([0x0C; 32]);
+        let treasury_ata_y1 = Pubkey::new_from_array([0x0D; 32]);
+        let treasury_ata_y2 = Pubkey::new_from_array([0x1D; 32]);
+
+        let seed = 7u64;
+        let (basket, basket_bump) = client::basket_address(&maker, seed);
+        let (vault_x1, vault_x1_bump) = client::vault_address(&basket, &mint_x1);
+        let (vault_x2, vault_x2_bump) = client::vault_address(&basket, &mint_x2);
+
+        let make = client::make_basket_ix(&MakeBasketParams {
+            maker,
+            seed,
+            expiry: 0,
+            taker: None,
+            offered: vec![
+                BasketDeposit { mint: mint_x1, maker_ata: maker_ata_x1, token_program, amount: 1_000_000 },
+                BasketDeposit { mint: mint_x2, maker_ata: maker_ata_x2, token_program, amount: 2_000_000 },
+            ],
+            requested: vec![
+                BasketRequest { mint: mint_y1, amount: 3_000_000 },
+                BasketRequest { mint: mint_y2, amount: 4_000_000 },
+            ],
+        });
+
+        let take = client::take_basket_ix(&TakeBasketParams {
+            taker,
+            maker,
+            seed,
+            requested: vec![
+                BasketPayment { mint: mint_y1, taker_ata: taker_ata_y1, maker_ata: maker_ata_y1, treasury: treasury_ata_y1, token_program },
+                BasketPayment { mint: mint_y2, taker_ata: taker_ata_y2, maker_ata: maker_ata_y2, treasury: treasury_ata_y2, token_program },
+            ],
+            offered: vec![
+                BasketReceipt { mint: mint_x1, token_account: taker_ata_x1, token_program },
+                BasketReceipt { mint: mint_x2, token_account: taker_ata_x2, token_program },
+            ],
+        });
+
+        let refund = client::refund_basket_ix(&RefundBasketParams {
+            maker,
+            seed,
+            offered: vec![
+                BasketReceipt { mint: mint_x1, token_account: maker_ata_x1, token_program },
+                BasketReceipt { mint: mint_x2, token_account: maker_ata_x2, token_program },
+            ],
+        });
+
+        let accounts = [
+            (maker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (taker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (basket, AccountSharedData::new(0, 0, &system_program)),
+            (mint_x1, mint_account(&mollusk)),
+            (mint_x2, mint_account(&mollusk)),
+            (mint_y1, mint_account(&mollusk)),
+            (mint_y2, mint_account(&mollusk)),
+            (maker_ata_x1, token_account(&mollusk, mint_x1, maker, 1_000_000)),
+            (maker_ata_x2, token_account(&mollusk, mint_x2, maker, 2_000_000)),
+            (maker_ata_y1, token_account(&mollusk, mint_y1, maker, 0)),
+            (maker_ata_y2, token_account(&mollusk, mint_y2, maker, 0)),
+            (taker_ata_x1, token_account(&mollusk, mint_x1, taker, 0)),
+            (taker_ata_x2, token_account(&mollusk, mint_x2, taker, 0)),
+            (taker_ata_y1, token_account(&mollusk, mint_y1, taker, 3_000_000)),
+            (taker_ata_y2, token_account(&mollusk, mint_y2, taker, 4_000_000)),
+            (treasury_ata_y1, token_account(&mollusk, mint_y1, treasury, 0)),
+            (treasury_ata_y2, token_account(&mollusk, mint_y2, treasury, 0)),
+            (vault_x1, AccountSharedData::new(0, 0, &system_program)),
+            (vault_x2, AccountSharedData::new(0, 0, &system_program)),
+            (system_program, system_account),
+            (token_program, token_program_account),
+            config_account(&mollusk, 100, 0, treasury),
+        ];
+
+        // Each offered mint is deposited in its own vault.
+        let result = mollusk.process_and_validate_instruction(&make, &accounts, &[Check::success()]);
+
+        let mut offered = [BasketLeg::default(); 4];
+        offered[0] = BasketLeg { mint: mint_x1.to_bytes(), amount: 1_000_000 };
+        offered[1] = BasketLeg { mint: mint_x2.to_bytes(), amount: 2_000_000 };
+        let mut requested = [BasketLeg::default(); 4];
+        requested[0] = BasketLeg { mint: mint_y1.to_bytes(), amount: 3_000_000 };
+        requested[1] = BasketLeg { mint: mint_y2.to_bytes(), amount: 4_000_000 };
+        let mut vault_bumps = [0; 4];
+        vault_bumps[0] = vault_x1_bump;
+        vault_bumps[1] = vault_x2_bump;
+
+        let expected = Basket {
+            maker: maker.to_bytes(),
+            seed,
+            offered,
+            requested,
+            offered_len: 2,
+            requested_len: 2,
+            bump: basket_bump,
+            vault_bumps,
+            ..Default::default()
+        };
+        assert_eq!(client::decode_basket(result.get_account(&basket).unwrap().data()), Some(expected));
+        assert_eq!(token_amount(result.get_account(&vault_x1).unwrap()), 1_000_000);
+        assert_eq!(token_amount(result.get_account(&vault_x2).unwrap()), 2_000_000);
+
+        // A single take settles every leg, less the 1% protocol fee on each requested mint,
+        // and closes the vaults and the basket.
+        let result = mollusk.process_and_validate_instruction_chain(
+            &[make.clone(), take],
+            &accounts,
+            &[
+                Check::success(),
+                Check::account(&vault_x1).lamports(0).build(),
+                Check::account(&vault_x2).lamports(0).build(),
+                Check::account(&basket).lamports(0).build(),
+            ],
+        );
+
+        assert_eq!(token_amount(result.get_account(&taker_ata_x1).unwrap()), 1_000_000);
+        assert_eq!(token_amount(result.get_account(&taker_ata_x2).unwrap()), 2_000_000);
+        assert_eq!(token_amount(result.get_account(&maker_ata_y1).unwrap()), 2_970_000);
+        assert_eq!(token_amount(result.get_account(&maker_ata_y2).unwrap()), 3_960_000);
+        assert_eq!(token_amount(result.get_account(&treasury_ata_y1).unwrap()), 30_000);
+        assert_eq!(token_amount(result.get_account(&treasury_ata_y2).unwrap()), 40_000);
+
+        // Refunding returns every offered mint instead.
+        let result = mollusk.process_and_validate_instruction_chain(
+            &[make, refund],
+            &accounts,
+            &[
+                Check::success(),
+                Check::account(&vault_x1).lamports(0).build(),
+                Check::account(&vault_x2).lamports(0).build(),
+                Check::account(&basket).lamports(0).build(),
+            ],
+        );
+
+        assert_eq!(token_amount(result.get_account(&maker_ata_x1).unwrap()), 1_000_000);
+        assert_eq!(token_amount(result.get_account(&maker_ata_x2).unwrap()), 2_000_000);
+    }
+
+    #[test]
+    fn test_nft_escrow() {
+        let (mollusk, (system_program, system_account), (token_program, token_program_account)) = setup();
+
+        let maker = Pubkey::new_from_array([0x02; 32]);
+        let taker = Pubkey::new_from_array([0x08; 32]);
+
+        // One NFT offered for another, which has to belong to the collection.
+        let mint_x = Pubkey::new_from_array([0x03; 32]);
+        let mint_y = Pubkey::new_from_array([0x04; 32]);
+        let collection = Pubkey::new_from_array([0x0C; 32]);
+        let mint_y_metadata = client::metadata_address(&mint_y);
+
+        let maker_ata_x = Pubkey::new_from_array([0x05; 32]);
+        let maker_ata_y = Pubkey::new_from_array([0x0B; 32]);
+        let taker_ata_x = Pubkey::new_from_array([0x09; 32]);
+        let taker_ata_y = Pubkey::new_from_array([0x0A; 32]);
+
+        let seed = 7u64;
+        let (escrow, _) = client::escrow_address(&maker, seed);
+        let (vault, _) = client::vault_address(&escrow, &mint_x);
+
+        let make = |deposit| client::make_ix(&MakeParams {
+            maker,
+            mint_x,
+            mint_y,
+            maker_ata_x,
+            token_program,
+            seed,
+            amount: 1,
+            deposit,
+            expiry: 0,
+            taker: None,
+            flags: NFT_X | NFT_Y,
+            collection: Some(collection),
+            arbiter: None,
+            hashlock: None,
+            vesting: None,
+            auction: None,
+            dutch: None,
+        });
+
+        let take = client::take_ix(&TakeParams {
+            taker,
+            maker,
+            seed,
+            mint_x,
+            mint_y,
+            taker_ata_x,
+            taker_ata_y,
+            maker_ata_y,
+            token_program_x: token_program,
+            token_program_y: token_program,
+            fill: 1,
+            version: 0,
+            treasury: system_program,
+            referrer: None,
+            referrer_bps: 0,
+            multisig: false,
+        });
+
+        let accounts = [
+            (maker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (taker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (mint_x, nft_mint_account(&mollusk)),
+            (mint_y, nft_mint_account(&mollusk)),
+            (mint_y_metadata, metadata_account(mint_y, collection, true)),
+            (maker_ata_x, token_account(&mollusk, mint_x, maker, 1)),
+            (maker_ata_y, token_account(&mollusk, mint_y, maker, 0)),
+            (taker_ata_x, token_account(&mollusk, mint_x, taker, 0)),
+            (taker_ata_y, token_account(&mollusk, mint_y, taker, 1)),
+            (vault, AccountSharedData::new(0, 0, &system_program)),
+            (escrow, AccountSharedData::new(0, 0, &system_program)),
+            (system_program, system_account),
+            (token_program, token_program_account),
+            config_account(&mollusk, 0, 0, system_program),
+        ];
+
+        // NFTs are only traded one at a time.
+        mollusk.process_and_validate_instruction(
+            &make(2),
+            &accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::InvalidNftAmount as u32))],
+        );
+
+        // A fungible mint isn't accepted as an NFT.
+        let mut fungible = accounts.clone();
+        fungible[2].1 = mint_account(&mollusk);
+        mollusk.process_and_validate_instruction(
+            &make(1),
+            &fungible,
+            &[Check::err(ProgramError::Custom(EscrowError::NotAnNft as u32))],
+        );
+
+        // mint_y has to be a verified member of the collection.
+        let mut unverified = accounts.clone();
+        unverified[4].1 = metadata_account(mint_y, collection, false);
+        mollusk.process_and_validate_instruction(
+            &make(1),
+            &unverified,
+            &[Check::err(ProgramError::Custom(EscrowError::CollectionMismatch as u32))],
+        );
+
+        // The NFTs swap owners and the vault and escrow are closed.
+        let result = mollusk.process_and_validate_instruction_chain(
+            &[make(1), take],
+            &accounts,
+            &[
+                Check::success(),
+                Check::account(&vault).lamports(0).build(),
+                Check::account(&escrow).lamports(0).build(),
+            ],
+        );
+
+        assert_eq!(token_amount(result.get_account(&taker_ata_x).unwrap()), 1);
+        assert_eq!(token_amount(result.get_account(&maker_ata_y).unwrap()), 1);
+    }
+
+    #[test]
+    fn test_token_2022_transfer_fee() {
+        let (mut mollusk, (system_program, system_account), (token_program, token_program_account)) = setup();
+
+        // Dumped from mainnet alongside the SPL token program, see the README.
+        mollusk.add_program(
+            &spl_token_2022::ID,
+            "programs/spl_token_2022-4.0.0",
+            &mollusk_svm::program::loader_keys::LOADER_V3,
+        );
+        let token_2022_program_account = program::create_program_account_loader_v3(&spl_token_2022::ID);
+
+        let maker = Pubkey::new_from_array([0x02; 32]);
+        let taker = Pubkey::new_from_array([0x08; 32]);
+
+        // mint_x is a Token-2022 mint with a 1% transfer fee, mint_y a plain SPL token.
+        let mint_x = Pubkey::new_from_array([0x03; 32]);
+        let mint_y = Pubkey::new_from_array([0x04; 32]);
+
+        let maker_ata_x = Pubkey::new_from_array([0x05; 32]);
+        let maker_ata_y = Pubkey::new_from_array([0x0B; 32]);
+        let taker_ata_x = Pubkey::new_from_array([0x09; 32]);
+        let taker_ata_y = Pubkey::new_from_array([0x0A; 32]);
+
+        let seed = 7u64;
+        let (escrow, escrow_bump) = client::escrow_address(&maker, seed);
+        let (vault, vault_bump) = client::vault_address(&escrow, &mint_x);
+
+        let make = client::make_ix(&MakeParams {
+            maker,
+            mint_x,
+            mint_y,
+            maker_ata_x,
+            token_program: spl_token_2022::ID,
+            seed,
+            amount: 1_000_000,
+            deposit: 1_000_000,
+            expiry: 0,
+            taker: None,
+            flags: 0,
+            collection: None,
+            arbiter: None,
+            hashlock: None,
+            vesting: None,
+            auction: None,
+            dutch: None,
+        });
+
+        let take = client::take_ix(&TakeParams {
+            taker,
+            maker,
+            seed,
+            mint_x,
+            mint_y,
+            taker_ata_x,
+            taker_ata_y,
+            maker_ata_y,
+            token_program_x: spl_token_2022::ID,
+            token_program_y: token_program,
+            fill: 1_000_000,
+            version: 0,
+            treasury: system_program,
+            referrer: None,
+            referrer_bps: 0,
+            multisig: false,
+        });
+
+        let accounts = [
+            (maker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (taker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (mint_x, mint_2022_account(&mollusk, 100)),
+            (mint_y, mint_account(&mollusk)),
+            (maker_ata_x, token_2022_account(&mollusk, mint_x, maker, 100_000_000)),
+            (maker_ata_y, token_account(&mollusk, mint_y, maker, 0)),
+            (taker_ata_x, token_2022_account(&mollusk, mint_x, taker, 0)),
+            (taker_ata_y, token_account(&mollusk, mint_y, taker, 1_000_000)),
+            (vault, AccountSharedData::new(0, 0, &system_program)),
+            (escrow, AccountSharedData::new(0, 0, &system_program)),
+            (system_program, system_account),
+            (token_program, token_program_account),
+            config_account(&mollusk, 0, 0, system_program),
+            (spl_token_2022::ID, token_2022_program_account),
+        ];
+
+        // The vault only receives 990,000 of the 1,000,000 deposited, which is what the escrow records.
+        let expected_escrow = escrow_account_data(&Escrow {
+            maker: maker.to_bytes(),
+            mint_x: mint_x.to_bytes(),
+            mint_y: mint_y.to_bytes(),
+            seed,
+            amount: 1_000_000,
+            deposit: 990_000,
+            bump: escrow_bump,
+            vault_bump,
+            ..Default::default()
+        });
+
+        let result = mollusk.process_and_validate_instruction(
+            &make,
+            &accounts,
+            &[
+                Check::success(),
+                Check::account(&escrow).data(&expected_escrow).build(),
+            ],
+        );
+
+        assert_eq!(token_amount(result.get_account(&vault).unwrap()), 990_000);
+
+        // Taking releases the full 990,000, of which the taker receives 980,100 after the fee,
+        // and the withheld fees are harvested so the vault can be closed.
+        let result = mollusk.process_and_validate_instruction_chain(
+            &[make, take],
+            &accounts,
+            &[
+                Check::success(),
+                Check::account(&vault).lamports(0).build(),
+                Check::account(&escrow).lamports(0).build(),
+            ],
+        );
+
+        assert_eq!(token_amount(result.get_account(&taker_ata_x).unwrap()), 980_100);
+        assert_eq!(token_amount(result.get_account(&maker_ata_y).unwrap()), 1_000_000);
+    }
+
+    #[test]
+    fn test_sol_for_token() {
+        let (mollusk, (system_program, system_account), (token_program, token_program_account)) = setup();
+
+        let maker = Pubkey::new_from_array([0x02; 32]);
+        let taker = Pubkey::new_from_array([0x08; 32]);
+
+        // The system program stands in for mint_x, the maker offers lamports.
+        let mint_y = Pubkey::new_from_array([0x04; 32]);
+
+        let maker_ata_y = Pubkey::new_from_array([0x0B; 32]);
+        let taker_ata_y = Pubkey::new_from_array([0x0A; 32]);
+
+        let seed = 7u64;
+        let (escrow, _) = client::escrow_address(&maker, seed);
+        let escrow_rent = mollusk.sysvars.rent.minimum_balance(Escrow::LEN);
+
+        // No token accounts are needed for the lamport side, the system program is passed in their place.
+        let make = client::make_ix(&MakeParams {
+            maker,
+            mint_x: system_program,
+            mint_y,
+            maker_ata_x: system_program,
+            token_program,
+            seed,
+            amount: 1_000_000,
+            deposit: LAMPORTS_PER_SOL / 2,
+            expiry: 0,
+            taker: None,
+            flags: 0,
+            collection: None,
+            arbiter: None,
+            hashlock: None,
+            vesting: None,
+            auction: None,
+            dutch: None,
+        });
+
+        let take = client::take_ix(&TakeParams {
+            taker,
+            maker,
+            seed,
+            mint_x: system_program,
+            mint_y,
+            taker_ata_x: system_program,
+            taker_ata_y,
+            maker_ata_y,
+            token_program_x: token_program,
+            token_program_y: token_program,
+            fill: 1_000_000,
+            version: 0,
+            treasury: system_program,
+            referrer: None,
+            referrer_bps: 0,
+            multisig: false,
+        });
+
+        let accounts = [
+            (maker, AccountSharedData::new(10 * LAMPORTS_PER_SOL, 0, &system_program)),
+            (taker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (mint_y, mint_account(&mollusk)),
+            (maker_ata_y, token_account(&mollusk, mint_y, maker, 0)),
+            (taker_ata_y, token_account(&mollusk, mint_y, taker, 1_000_000)),
+            (escrow, AccountSharedData::new(0, 0, &system_program)),
+            (system_program, system_account),
+            (token_program, token_program_account),
+            config_account(&mollusk, 0, 0, system_program),
+        ];
+
+        // The offered lamports sit in the escrow account next to its rent.
+        mollusk.process_and_validate_instruction(
+            &make,
+            &accounts,
+            &[
+                Check::success(),
+                Check::account(&escrow).lamports(escrow_rent + LAMPORTS_PER_SOL / 2).build(),
+            ],
+        );
+
+        let result = mollusk.process_and_validate_instruction_chain(
+            &[make, take],
+            &accounts,
+            &[
+                Check::success(),
+                Check::account(&escrow).lamports(0).build(),
+                Check::account(&taker).lamports(LAMPORTS_PER_SOL + LAMPORTS_PER_SOL / 2).build(),
+                Check::account(&maker).lamports(10 * LAMPORTS_PER_SOL - LAMPORTS_PER_SOL / 2).build(),
+            ],
+        );
+
+        assert_eq!(token_amount(result.get_account(&maker_ata_y).unwrap()), 1_000_000);
+    }
+
+    #[test]
+    fn test_token_for_sol() {
+        let (mollusk, (system_program, system_account), (token_program, token_program_account)) = setup();
+
+        let maker = Pubkey::new_from_array([0x02; 32]);
+        let taker = Pubkey::new_from_array([0x08; 32]);
+
+        // The system program stands in for mint_y, the maker asks for lamports.
+        let mint_x = Pubkey::new_from_array([0x03; 32]);
+        let taker_ata_x = Pubkey::new_from_array([0x09; 32]);
+
+        let seed = 7u64;
+        let (escrow, escrow_bump) = client::escrow_address(&maker, seed);
+        let (vault, vault_bump) = client::vault_address(&escrow, &mint_x);
+
+        let escrow_state = Escrow {
+            maker: maker.to_bytes(),
+            mint_x: mint_x.to_bytes(),
+            mint_y: system_program.to_bytes(),
+            seed,
+            amount: LAMPORTS_PER_SOL / 4,
+            deposit: 2_000_000,
+            bump: escrow_bump,
+            vault_bump,
+            ..Default::default()
+        };
+
+        let take = client::take_ix(&TakeParams {
+            taker,
+            maker,
+            seed,
+            mint_x,
+            mint_y: system_program,
+            taker_ata_x,
+            taker_ata_y: system_program,
+            maker_ata_y: system_program,
+            token_program_x: token_program,
+            token_program_y: token_program,
+            fill: LAMPORTS_PER_SOL / 4,
+            version: 0,
+            treasury: system_program,
+            referrer: None,
+            referrer_bps: 0,
+            multisig: false,
+        });
+
+        let vault_account = token_account(&mollusk, mint_x, escrow, 2_000_000);
+        let escrow_state_account = escrow_account(&mollusk, &escrow_state);
+        let maker_refunded = LAMPORTS_PER_SOL + vault_account.lamports() + escrow_state_account.lamports();
+
+        let result = mollusk.process_and_validate_instruction(
+            &take,
+            &[
+                (taker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+                (maker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+                (mint_x, mint_account(&mollusk)),
+                (taker_ata_x, token_account(&mollusk, mint_x, taker, 0)),
+                (vault, vault_account),
+                (escrow, escrow_state_account),
+                (system_program, system_account),
+                (token_program, token_program_account),
+                config_account(&mollusk, 0, 0, system_program),
+            ],
+            &[
+                Check::success(),
+                Check::account(&taker).lamports(LAMPORTS_PER_SOL - LAMPORTS_PER_SOL / 4).build(),
+                Check::account(&maker).lamports(maker_refunded + LAMPORTS_PER_SOL / 4).build(),
+            ],
+        );
+
+        assert_eq!(token_amount(result.get_account(&taker_ata_x).unwrap()), 2_000_000);
+    }
+
+    #[test]
+    fn test_protocol_fee() {
+        let (mollusk, (system_program, system_account), (token_program, token_program_account)) = setup();
+
+        let admin = Pubkey::new_from_array([0x01; 32]);
+        let treasury = Pubkey::new_from_array([0x0C; 32]);
+        let (config, config_bump) = client::config_address();
+
+        let outsider = Pubkey::new_from_array([0x0E; 32]);
+
+        let init_accounts = [
+            (admin, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (outsider, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (config, AccountSharedData::new(0, 0, &system_program)),
+            (system_program, system_account.clone()),
+            program_data_account(admin),
+        ];
+
+        // Only the upgrade authority of the program can create the config.
+        mollusk.process_and_validate_instruction(
+            &client::init_config_ix(&InitConfigParams { admin: outsider, fee_bps: 100, max_referrer_bps: 50, treasury }),
+            &init_accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::UnauthorizedAdmin as u32))],
+        );
+
+        // The fee can't be more than the whole payment.
+        mollusk.process_and_validate_instruction(
+            &client::init_config_ix(&InitConfigParams { admin, fee_bps: 10_001, max_referrer_bps: 0, treasury }),
+            &init_accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::InvalidFee as u32))],
+        );
+
+        let result = mollusk.process_and_validate_instruction(
+            &client::init_config_ix(&InitConfigParams { admin, fee_bps: 100, max_referrer_bps: 50, treasury }),
+            &init_accounts,
+            &[Check::success(), Check::account(&config).owner(&ID).build()],
+        );
+
+        let expected = Config {
+            admin: admin.to_bytes(),
+            treasury: treasury.to_bytes(),
+            fee_bps: 100,
+            max_referrer_bps: 50,
+            bump: config_bump,
+            ..Default::default()
+        };
+        assert_eq!(client::decode_config(result.get_account(&config).unwrap().data()), Some(expected));
+
+        let maker = Pubkey::new_from_array([0x02; 32]);
+        let taker = Pubkey::new_from_array([0x08; 32]);
+        let mint_x = Pubkey::new_from_array([0x03; 32]);
+        let mint_y = Pubkey::new_from_array([0x04; 32]);
+        let taker_ata_x = Pubkey::new_from_array([0x09; 32]);
+        let taker_ata_y = Pubkey::new_from_array([0x0A; 32]);
+        let maker_ata_y = Pubkey::new_from_array([0x0B; 32]);
+        let treasury_ata = Pubkey::new_from_array([0x0D; 32]);
+
+        let seed = 7u64;
+        let (escrow, escrow_bump) = client::escrow_address(&maker, seed);
+        let (vault, vault_bump) = client::vault_address(&escrow, &mint_x);
+
+        let escrow_state = Escrow {
+            maker: maker.to_bytes(),
+            mint_x: mint_x.to_bytes(),
+            mint_y: mint_y.to_bytes(),
+            seed,
+            amount: 1_000_000,
+            deposit: 2_000_000,
+            bump: escrow_bump,
+            vault_bump,
+            ..Default::default()
+        };
+
+        let take = |treasury_ata| client::take_ix(&TakeParams {
+            taker,
+            maker,
+            seed,
+            mint_x,
+            mint_y,
+            taker_ata_x,
+            taker_ata_y,
+            maker_ata_y,
+            token_program_x: token_program,
+            token_program_y: token_program,
+            fill: 1_000_000,
+            version: 0,
+            treasury: treasury_ata,
+            referrer: None,
+            referrer_bps: 0,
+            multisig: false,
+        });
+
+        let accounts = [
+            (taker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (maker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (mint_x, mint_account(&mollusk)),
+            (mint_y, mint_account(&mollusk)),
+            (taker_ata_x, token_account(&mollusk, mint_x, taker, 0)),
+            (taker_ata_y, token_account(&mollusk, mint_y, taker, 1_000_000)),
+            (maker_ata_y, token_account(&mollusk, mint_y, maker, 0)),
+            (treasury_ata, token_account(&mollusk, mint_y, treasury, 0)),
+            (vault, token_account(&mollusk, mint_x, escrow, 2_000_000)),
+            (escrow, escrow_account(&mollusk, &escrow_state)),
+            (system_program, system_account),
+            (token_program, token_program_account),
+            (config, result.get_account(&config).unwrap().clone()),
+        ];
+
+        // The fee has to go to a token account of the configured treasury.
+        mollusk.process_and_validate_instruction(
+            &take(maker_ata_y),
+            &accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::InvalidTokenAccount as u32))],
+        );
+
+        // 1% of the payment goes to the treasury, the maker receives the rest.
+        let result = mollusk.process_and_validate_instruction(&take(treasury_ata), &accounts, &[Check::success()]);
+
+        assert_eq!(token_amount(result.get_account(&taker_ata_x).unwrap()), 2_000_000);
+        assert_eq!(token_amount(result.get_account(&taker_ata_y).unwrap()), 0);
+        assert_eq!(token_amount(result.get_account(&maker_ata_y).unwrap()), 990_000);
+        assert_eq!(token_amount(result.get_account(&treasury_ata).unwrap()), 10_000);
+    }
+
+    #[test]
+    fn test_pause() {
+        let (mollusk, (system_program, system_account), (token_program, token_program_account)) = setup();
+
+        // The config created by `config_account` is administered by 0x01.
+        let admin = Pubkey::new_from_array([0x01; 32]);
+        let new_admin = Pubkey::new_from_array([0x0E; 32]);
+        let (config, config_data) = config_account(&mollusk, 0, 0, system_program);
+
+        let maker = Pubkey::new_from_array([0x02; 32]);
+        let taker = Pubkey::new_from_array([0x08; 32]);
+        let mint_x = Pubkey::new_from_array([0x03; 32]);
+        let mint_y = Pubkey::new_from_array([0x04; 32]);
+        let maker_ata_x = Pubkey::new_from_array([0x05; 32]);
+        let taker_ata_x = Pubkey::new_from_array([0x09; 32]);
+        let taker_ata_y = Pubkey::new_from_array([0x0A; 32]);
+        let maker_ata_y = Pubkey::new_from_array([0x0B; 32]);
+
+        let seed = 7u64;
+        let (escrow, escrow_bump) = client::escrow_address(&maker, seed);
+        let (vault, vault_bump) = client::vault_address(&escrow, &mint_x);
+
+        let escrow_state = Escrow {
+            maker: maker.to_bytes(),
+            mint_x: mint_x.to_bytes(),
+            mint_y: mint_y.to_bytes(),
+            seed,
+            amount: 1_000_000,
+            deposit: 2_000_000,
+            bump: escrow_bump,
+            vault_bump,
+            ..Default::default()
+        };
+
+        let mut accounts = vec![
+            (admin, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (new_admin, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (taker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (maker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (mint_x, mint_account(&mollusk)),
+            (mint_y, mint_account(&mollusk)),
+            (maker_ata_x, token_account(&mollusk, mint_x, maker, 0)),
+            (taker_ata_x, token_account(&mollusk, mint_x, taker, 0)),
+            (taker_ata_y, token_account(&mollusk, mint_y, taker, 1_000_000)),
+            (maker_ata_y, token_account(&mollusk, mint_y, maker, 0)),
+            (vault, token_account(&mollusk, mint_x, escrow, 2_000_000)),
+            (escrow, escrow_account(&mollusk, &escrow_state)),
+            (system_program, system_account),
+            (token_program, token_program_account),
+            (config, config_data),
+        ];
+
+        // Only the admin can pause.
+        mollusk.process_and_validate_instruction(
+            &client::set_paused_ix(&new_admin, true),
+            &accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::UnauthorizedAdmin as u32))],
+        );
+
+        let result = mollusk.process_and_validate_instruction(&client::set_paused_ix(&admin, true), &accounts, &[Check::success()]);
+        assert!(client::decode_config(result.get_account(&config).unwrap().data()).unwrap().is_paused());
+        accounts.last_mut().unwrap().1 = result.get_account(&config).unwrap().clone();
+
+        // Takes are refused while paused, but the maker can still get the deposit back.
+        mollusk.process_and_validate_instruction(
+            &take_instruction(taker, maker, seed, mint_x, mint_y, taker_ata_x, taker_ata_y, maker_ata_y, 1_000_000),
+            &accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::Paused as u32))],
+        );
+
+        let result = mollusk.process_and_validate_instruction(
+            &refund_instruction(maker, seed, mint_x, maker_ata_x),
+            &accounts,
+            &[Check::success()],
+        );
+        assert_eq!(token_amount(result.get_account(&maker_ata_x).unwrap()), 2_000_000);
+
+        // Once handed over, the previous admin loses control of the config.
+        let result = mollusk.process_and_validate_instruction(&client::set_admin_ix(&admin, &new_admin), &accounts, &[Check::success()]);
+        assert_eq!(client::decode_config(result.get_account(&config).unwrap().data()).unwrap().admin, new_admin.to_bytes());
+        accounts.last_mut().unwrap().1 = result.get_account(&config).unwrap().clone();
+
+        mollusk.process_and_validate_instruction(
+            &client::set_paused_ix(&admin, false),
+            &accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::UnauthorizedAdmin as u32))],
+        );
+
+        let result = mollusk.process_and_validate_instruction(&client::set_paused_ix(&new_admin, false), &accounts, &[Check::success()]);
+        accounts.last_mut().unwrap().1 = result.get_account(&config).unwrap().clone();
+
+        mollusk.process_and_validate_instruction(
+            &take_instruction(taker, maker, seed, mint_x, mint_y, taker_ata_x, taker_ata_y, maker_ata_y, 1_000_000),
+            &accounts,
+            &[Check::success()],
+        );
+    }
+
+    #[test]
+    fn test_referrer_fee() {
+        let (mollusk, (system_program, system_account), (token_program, token_program_account)) = setup();
+
+        let treasury = Pubkey::new_from_array([0x0C; 32]);
+        let referrer = Pubkey::new_from_array([0x0F; 32]);
+
+        let maker = Pubkey::new_from_array([0x02; 32]);
+        let taker = Pubkey::new_from_array([0x08; 32]);
+        let mint_x = Pubkey::new_from_array([0x03; 32]);
+        let mint_y = Pubkey::new_from_array([0x04; 32]);
+        let taker_ata_x = Pubkey::new_from_array([0x09; 32]);
+        let taker_ata_y = Pubkey::new_from_array([0x0A; 32]);
+        let maker_ata_y = Pubkey::new_from_array([0x0B; 32]);
+        let treasury_ata = Pubkey::new_from_array([0x0D; 32]);
+        let referrer_ata = Pubkey::new_from_array([0x10; 32]);
+
+        let seed = 7u64;
+        let (escrow, escrow_bump) = client::escrow_address(&maker, seed);
+        let (vault, vault_bump) = client::vault_address(&escrow, &mint_x);
+
+        let escrow_state = Escrow {
+            maker: maker.to_bytes(),
+            mint_x: mint_x.to_bytes(),
+            mint_y: mint_y.to_bytes(),
+            seed,
+            amount: 1_000_000,
+            deposit: 2_000_000,
+            bump: escrow_bump,
+            vault_bump,
+            ..Default::default()
+        };
+
+        let take = |referrer, referrer_bps| client::take_ix(&TakeParams {
+            taker,
+            maker,
+            seed,
+            mint_x,
+            mint_y,
+            taker_ata_x,
+            taker_ata_y,
+            maker_ata_y,
+            token_program_x: token_program,
+            token_program_y: token_program,
+            fill: 1_000_000,
+            version: 0,
+            treasury: treasury_ata,
+            referrer,
+            referrer_bps,
+            multisig: false,
+        });
+
+        let accounts = [
+            (taker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (maker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (mint_x, mint_account(&mollusk)),
+            (mint_y, mint_account(&mollusk)),
+            (taker_ata_x, token_account(&mollusk, mint_x, taker, 0)),
+            (taker_ata_y, token_account(&mollusk, mint_y, taker, 1_000_000)),
+            (maker_ata_y, token_account(&mollusk, mint_y, maker, 0)),
+            (treasury_ata, token_account(&mollusk, mint_y, treasury, 0)),
+            (referrer_ata, token_account(&mollusk, mint_y, referrer, 0)),
+            (vault, token_account(&mollusk, mint_x, escrow, 2_000_000)),
+            (escrow, escrow_account(&mollusk, &escrow_state)),
+            (system_program, system_account),
+            (token_program, token_program_account),
+            config_account(&mollusk, 100, 50, treasury),
+        ];
+
+        // The referrer can't get more than the config allows.
+        mollusk.process_and_validate_instruction(
+            &take(Some(referrer_ata), 51),
+            &accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::InvalidReferrerFee as u32))],
+        );
+
+        // A referrer fee needs an account to be paid to.
+        mollusk.process_and_validate_instruction(
+            &take(None, 50),
+            &accounts,
+            &[Check::err(ProgramError::NotEnoughAccountKeys)],
+        );
+
+        // 1% goes to the treasury and 0.5% to the referrer, the maker receives the rest.
+        let result = mollusk.process_and_validate_instruction(&take(Some(referrer_ata), 50), &accounts, &[Check::success()]);
+
+        assert_eq!(token_amount(result.get_account(&taker_ata_x).unwrap()), 2_000_000);
+        assert_eq!(token_amount(result.get_account(&taker_ata_y).unwrap()), 0);
+        assert_eq!(token_amount(result.get_account(&maker_ata_y).unwrap()), 985_000);
+        assert_eq!(token_amount(result.get_account(&treasury_ata).unwrap()), 10_000);
+        assert_eq!(token_amount(result.get_account(&referrer_ata).unwrap()), 5_000);
+    }
+
+    #[test]
+    fn test_dispute() {
+        let (mollusk, (system_program, system_account), (token_program, token_program_account)) = setup();
+
+        let maker = Pubkey::new_from_array([0x02; 32]);
+        let taker = Pubkey::new_from_array([0x08; 32]);
+        let arbiter = Pubkey::new_from_array([0x11; 32]);
+        let outsider = Pubkey::new_from_array([0x12; 32]);
+
+        let mint_x = Pubkey::new_from_array([0x03; 32]);
+        let mint_y = Pubkey::new_from_array([0x04; 32]);
+        let maker_ata_x = Pubkey::new_from_array([0x05; 32]);
+        let taker_ata_x = Pubkey::new_from_array([0x09; 32]);
+        let taker_ata_y = Pubkey::new_from_array([0x0A; 32]);
+        let maker_ata_y = Pubkey::new_from_array([0x0B; 32]);
+
+        let seed = 7u64;
+        let (escrow, escrow_bump) = client::escrow_address(&maker, seed);
+        let (vault, vault_bump) = client::vault_address(&escrow, &mint_x);
+
+        let escrow_state = Escrow {
+            maker: maker.to_bytes(),
+            mint_x: mint_x.to_bytes(),
+            mint_y: mint_y.to_bytes(),
+            taker: taker.to_bytes(),
+            arbiter: arbiter.to_bytes(),
+            seed,
+            amount: 1_000_000,
+            deposit: 2_000_000,
+            bump: escrow_bump,
+            vault_bump,
+            ..Default::default()
+        };
+
+        let mut accounts = vec![
+            (maker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (taker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (arbiter, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (outsider, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (mint_x, mint_account(&mollusk)),
+            (mint_y, mint_account(&mollusk)),
+            (maker_ata_x, token_account(&mollusk, mint_x, maker, 0)),
+            (taker_ata_x, token_account(&mollusk, mint_x, taker, 0)),
+            (taker_ata_y, token_account(&mollusk, mint_y, taker, 1_000_000)),
+            (maker_ata_y, token_account(&mollusk, mint_y, maker, 0)),
+            (vault, token_account(&mollusk, mint_x, escrow, 2_000_000)),
+            (escrow, escrow_account(&mollusk, &escrow_state)),
+            (system_program, system_account),
+            (token_program, token_program_account),
+            config_account(&mollusk, 0, 0, system_program),
+        ];
+
+        // Only the maker and the designated taker can open a dispute.
+        mollusk.process_and_validate_instruction(
+            &client::dispute_ix(&outsider, &maker, seed),
+            &accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::NotAParty as u32))],
+        );
+
+        let result = mollusk.process_and_validate_instruction(&client::dispute_ix(&taker, &maker, seed), &accounts, &[Check::success()]);
+        assert_eq!(client::decode_escrow(result.get_account(&escrow).unwrap().data()).unwrap().status, STATUS_DISPUTED);
+        accounts[11].1 = result.get_account(&escrow).unwrap().clone();
+
+        // Neither party can settle on their own while the dispute is open.
+        mollusk.process_and_validate_instruction(
+            &take_instruction(taker, maker, seed, mint_x, mint_y, taker_ata_x, taker_ata_y, maker_ata_y, 1_000_000),
+            &accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::Disputed as u32))],
+        );
+        mollusk.process_and_validate_instruction(
+            &refund_instruction(maker, seed, mint_x, maker_ata_x),
+            &accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::Disputed as u32))],
+        );
+        mollusk.process_and_validate_instruction(
+            &client::deposit_ix(&ResizeParams {
+                maker,
+                seed,
+                mint_x,
+                maker_ata_x,
+                token_program,
+                amount: 1_000_000,
+            }),
+            &accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::Disputed as u32))],
+        );
+
+        let resolve = |arbiter, resolution| client::resolve_ix(&ResolveParams {
+            arbiter,
+            maker,
+            taker,
+            seed,
+            mint_x,
+            maker_ata_x,
+            taker_ata_x,
+            token_program,
+            resolution,
+            multisig: false,
+        });
+
+        mollusk.process_and_validate_instruction(
+            &resolve(maker, Resolution::Release),
+            &accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::InvalidArbiter as u32))],
+        );
+        mollusk.process_and_validate_instruction(
+            &resolve(arbiter, Resolution::Split(2_000_001)),
+            &accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::InvalidAmount as u32))],
+        );
+
+        // The arbiter splits the deposit and the escrow is closed.
+        let result = mollusk.process_and_validate_instruction(
+            &resolve(arbiter, Resolution::Split(600_000)),
+            &accounts,
+            &[
+                Check::success(),
+                Check::account(&vault).lamports(0).build(),
+                Check::account(&escrow).lamports(0).build(),
+            ],
+        );
+
+        assert_eq!(token_amount(result.get_account(&taker_ata_x).unwrap()), 600_000);
+        assert_eq!(token_amount(result.get_account(&maker_ata_x).unwrap()), 1_400_000);
+    }
+
+    #[test]
+    fn test_multisig() {
+        let (mollusk, (system_program, system_account), (token_program, token_program_account)) = setup();
+
+        let maker = Pubkey::new_from_array([0x02; 32]);
+        let taker = Pubkey::new_from_array([0x08; 32]);
+        let approvers = [
+            Pubkey::new_from_array([0x13; 32]),
+            Pubkey::new_from_array([0x14; 32]),
+            Pubkey::new_from_array([0x15; 32]),
+        ];
+        let outsider = Pubkey::new_from_array([0x12; 32]);
+
+        let mint_x = Pubkey::new_from_array([0x03; 32]);
+        let mint_y = Pubkey::new_from_array([0x04; 32]);
+        let taker_ata_x = Pubkey::new_from_array([0x09; 32]);
+        let taker_ata_y = Pubkey::new_from_array([0x0A; 32]);
+        let maker_ata_y = Pubkey::new_from_array([0x0B; 32]);
+
+        let seed = 7u64;
+        let (escrow, escrow_bump) = client::escrow_address(&maker, seed);
+        let (vault, vault_bump) = client::vault_address(&escrow, &mint_x);
+        let (approvals, approvals_bump) = client::approvals_address(&escrow);
+
+        let escrow_state = Escrow {
+            maker: maker.to_bytes(),
+            mint_x: mint_x.to_bytes(),
+            mint_y: mint_y.to_bytes(),
+            seed,
+            amount: 1_000_000,
+            deposit: 2_000_000,
+            bump: escrow_bump,
+            vault_bump,
+            ..Default::default()
+        };
+
+        let accounts = [
+            (maker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (taker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (approvers[0], AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (approvers[1], AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (approvers[2], AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (outsider, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (mint_x, mint_account(&mollusk)),
+            (mint_y, mint_account(&mollusk)),
+            (taker_ata_x, token_account(&mollusk, mint_x, taker, 0)),
+            (taker_ata_y, token_account(&mollusk, mint_y, taker, 1_000_000)),
+            (maker_ata_y, token_account(&mollusk, mint_y, maker, 0)),
+            (vault, token_account(&mollusk, mint_x, escrow, 2_000_000)),
+            (escrow, escrow_account(&mollusk, &escrow_state)),
+            (approvals, AccountSharedData::new(0, 0, &system_program)),
+            (system_program, system_account),
+            (token_program, token_program_account),
+            config_account(&mollusk, 0, 0, system_program),
+        ];
+
+        let init = |threshold, approvers: &[Pubkey]| client::init_approvals_ix(&InitApprovalsParams {
+            maker,
+            seed,
+            threshold,
+            approvers: approvers.to_vec(),
+        });
+        let approve = |approver| client::approve_ix(&approver, &maker, seed, 0);
+        let take = client::take_ix(&TakeParams {
+            taker,
+            maker,
+            seed,
+            mint_x,
+            mint_y,
+            taker_ata_x,
+            taker_ata_y,
+            maker_ata_y,
+            token_program_x: token_program,
+            token_program_y: token_program,
+            fill: 1_000_000,
+            version: 0,
+            treasury: system_program,
+            referrer: None,
+            referrer_bps: 0,
+            multisig: true,
+        });
+
+        // The threshold can't exceed the approvers, and each approver is listed once.
+        mollusk.process_and_validate_instruction(
+            &init(4, &approvers),
+            &accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::InvalidApprovals as u32))],
+        );
+        mollusk.process_and_validate_instruction(
+            &init(2, &[approvers[0], approvers[0]]),
+            &accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::InvalidApprovals as u32))],
+        );
+
+        let result = mollusk.process_and_validate_instruction(&init(2, &approvers), &accounts, &[Check::success()]);
+
+        let expected = Approvals {
+            escrow: escrow.to_bytes(),
+            approvers: [
+                approvers[0].to_bytes(),
+                approvers[1].to_bytes(),
+                approvers[2].to_bytes(),
+                [0; 32],
+                [0; 32],
+                [0; 32],
+                [0; 32],
+                [0; 32],
+            ],
+            approvers_len: 3,
+            threshold: 2,
+            bump: approvals_bump,
+            ..Default::default()
+        };
+        assert_eq!(client::decode_approvals(result.get_account(&approvals).unwrap().data()), Some(expected));
+        assert!(client::decode_escrow(result.get_account(&escrow).unwrap().data()).unwrap().is_multisig());
+
+        mollusk.process_and_validate_instruction_chain(
+            &[init(2, &approvers), approve(outsider)],
+            &accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::NotAnApprover as u32))],
+        );
+
+        // One approval out of two isn't enough to release the vault.
+        mollusk.process_and_validate_instruction_chain(
+            &[init(2, &approvers), approve(approvers[0]), take.clone()],
+            &accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::ApprovalsPending as u32))],
+        );
+
+        let result = mollusk.process_and_validate_instruction_chain(
+            &[init(2, &approvers), approve(approvers[0]), approve(approvers[2]), take],
+            &accounts,
+            &[
+                Check::success(),
+                Check::account(&vault).lamports(0).build(),
+                Check::account(&escrow).lamports(0).build(),
+                Check::account(&approvals).lamports(0).build(),
+            ],
+        );
+
+        assert_eq!(token_amount(result.get_account(&taker_ata_x).unwrap()), 2_000_000);
+        assert_eq!(token_amount(result.get_account(&maker_ata_y).unwrap()), 1_000_000);
+    }
+
+    #[test]
+    fn test_htlc() {
+        let (mut mollusk, (system_program, system_account), (token_program, token_program_account)) = setup();
+
+        let maker = Pubkey::new_from_array([0x02; 32]);
+        let taker = Pubkey::new_from_array([0x08; 32]);
+        let mint_x = Pubkey::new_from_array([0x03; 32]);
+        let mint_y = Pubkey::new_from_array([0x04; 32]);
+        let maker_ata_x = Pubkey::new_from_array([0x05; 32]);
+        let taker_ata_x = Pubkey::new_from_array([0x09; 32]);
+        let taker_ata_y = Pubkey::new_from_array([0x0A; 32]);
+        let maker_ata_y = Pubkey::new_from_array([0x0B; 32]);
+
+        let preimage = [0x2A; 32];
+        let hashlock = solana_sdk::hash::hash(&preimage).to_bytes();
+
+        let seed = 7u64;
+        let (escrow, escrow_bump) = client::escrow_address(&maker, seed);
+        let (vault, vault_bump) = client::vault_address(&escrow, &mint_x);
+
+        mollusk.sysvars.clock.unix_timestamp = 500;
+
+        // The vault of a hash time-locked escrow is released to a designated taker.
+        mollusk.process_and_validate_instruction(
+            &client::make_ix(&MakeParams {
+                maker,
+                mint_x,
+                mint_y,
+                maker_ata_x,
+                token_program,
+                seed,
+                amount: 1,
+                deposit: 2_000_000,
+                expiry: 1_000,
+                taker: None,
+                flags: 0,
+                collection: None,
+                arbiter: None,
+                hashlock: Some(hashlock),
+                vesting: None,
+                auction: None,
+                dutch: None,
+            }),
+            &[
+                (maker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+                (mint_x, mint_account(&mollusk)),
+                (mint_y, mint_account(&mollusk)),
+                (maker_ata_x, token_account(&mollusk, mint_x, maker, 2_000_000)),
+                (vault, AccountSharedData::new(0, 0, &system_program)),
+                (escrow, AccountSharedData::new(0, 0, &system_program)),
+                (system_program, system_account.clone()),
+                (token_program, token_program_account.clone()),
+                config_account(&mollusk, 0, 0, system_program),
+            ],
+            &[Check::err(ProgramError::Custom(EscrowError::InvalidRecipient as u32))],
+        );
+
+        let escrow_state = Escrow {
+            maker: maker.to_bytes(),
+            mint_x: mint_x.to_bytes(),
+            mint_y: mint_y.to_bytes(),
+            taker: taker.to_bytes(),
+            seed,
+            amount: 1,
+            deposit: 2_000_000,
+            expiry: 1_000,
+            bump: escrow_bump,
+            vault_bump,
+            flags: HTLC,
+            ..Default::default()
+        };
+
+        let accounts = [
+            (maker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (taker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (mint_x, mint_account(&mollusk)),
+            (mint_y, mint_account(&mollusk)),
+            (maker_ata_x, token_account(&mollusk, mint_x, maker, 0)),
+            (taker_ata_x, token_account(&mollusk, mint_x, taker, 0)),
+            (taker_ata_y, token_account(&mollusk, mint_y, taker, 1)),
+            (maker_ata_y, token_account(&mollusk, mint_y, maker, 0)),
+            (vault, token_account(&mollusk, mint_x, escrow, 2_000_000)),
+            (escrow, escrow_section_account(&mollusk, &escrow_state, &Htlc { hashlock })),
+            (system_program, system_account),
+            (token_program, token_program_account),
+            config_account(&mollusk, 0, 0, system_program),
+        ];
+
+        let claim = |preimage| client::claim_ix(&ClaimParams {
+            maker,
+            taker,
+            seed,
+            mint_x,
+            taker_ata_x,
+            token_program,
+            preimage,
+        });
+        let refund = refund_instruction(maker, seed, mint_x, maker_ata_x);
+
+        // Before the timelock the escrow can't be taken or refunded, only claimed with the preimage.
+        mollusk.process_and_validate_instruction(
+            &take_instruction(taker, maker, seed, mint_x, mint_y, taker_ata_x, taker_ata_y, maker_ata_y, 1),
+            &accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::WrongEscrowType as u32))],
+        );
+        mollusk.process_and_validate_instruction(
+            &refund,
+            &accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::TimelockActive as u32))],
+        );
+        mollusk.process_and_validate_instruction(
+            &claim([0x2B; 32]),
+            &accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::InvalidPreimage as u32))],
+        );
+
+        let result = mollusk.process_and_validate_instruction(
+            &claim(preimage),
+            &accounts,
+            &[
+                Check::success(),
+                Check::account(&vault).lamports(0).build(),
+                Check::account(&escrow).lamports(0).build(),
+            ],
+        );
+        assert_eq!(token_amount(result.get_account(&taker_ata_x).unwrap()), 2_000_000);
+
+        // Once the timelock passes the preimage no longer works and the maker gets the deposit back.
+        mollusk.sysvars.clock.unix_timestamp = 1_000;
+
+        mollusk.process_and_validate_instruction(
+            &claim(preimage),
+            &accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::EscrowExpired as u32))],
+        );
+
+        let result = mollusk.process_and_validate_instruction(&refund, &accounts, &[Check::success()]);
+        assert_eq!(token_amount(result.get_account(&maker_ata_x).unwrap()), 2_000_000);
+    }
+
+    #[test]
+    fn test_htlc_token_2022() {
+        let (mut mollusk, (system_program, system_account), (token_program, token_program_account)) = setup();
+
+        mollusk.add_program(
+            &spl_token_2022::ID,
+            "programs/spl_token_2022-4.0.0",
+            &mollusk_svm::program::loader_keys::LOADER_V3,
+        );
+        let token_2022_program_account = program::create_program_account_loader_v3(&spl_token_2022::ID);
+
+        let maker = Pubkey::new_from_array([0x02; 32]);
+        let taker = Pubkey::new_from_array([0x08; 32]);
+        let mint_x = Pubkey::new_from_array([0x03; 32]);
+        let mint_y = Pubkey::new_from_array([0x04; 32]);
+        let maker_ata_x = Pubkey::new_from_array([0x05; 32]);
+        let taker_ata_x = Pubkey::new_from_array([0x09; 32]);
+
+        let preimage = [0x2A; 32];
+
+        let seed = 7u64;
+        let (escrow, _) = client::escrow_address(&maker, seed);
+        let (vault, _) = client::vault_address(&escrow, &mint_x);
+
+        mollusk.sysvars.clock.unix_timestamp = 500;
+
+        let make = client::make_ix(&MakeParams {
+            maker,
+            mint_x,
+            mint_y,
+            maker_ata_x,
+            token_program: spl_token_2022::ID,
+            seed,
+            amount: 1,
+            deposit: 1_000_000,
+            expiry: 1_000,
+            taker: Some(taker),
+            flags: 0,
+            collection: None,
+            arbiter: None,
+            hashlock: Some(solana_sdk::hash::hash(&preimage).to_bytes()),
+            vesting: None,
+            auction: None,
+            dutch: None,
+        });
+
+        let claim = client::claim_ix(&ClaimParams {
+            maker,
+            taker,
+            seed,
+            mint_x,
+            taker_ata_x,
+            token_program: spl_token_2022::ID,
+            preimage,
+        });
+
+        // The deposit leaves 10,000 of fees withheld in the vault, which the claim harvests
+        // to the mint before closing the vault.
+        let result = mollusk.process_and_validate_instruction_chain(
+            &[make, claim],
+            &[
+                (maker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+                (taker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+                (mint_x, mint_2022_account(&mollusk, 100)),
+                (mint_y, mint_account(&mollusk)),
+                (maker_ata_x, token_2022_account(&mollusk, mint_x, maker, 1_000_000)),
+                (taker_ata_x, token_2022_account(&mollusk, mint_x, taker, 0)),
+                (vault, AccountSharedData::new(0, 0, &system_program)),
+                (escrow, AccountSharedData::new(0, 0, &system_program)),
+                (system_program, system_account),
+                (token_program, token_program_account),
+                config_account(&mollusk, 0, 0, system_program),
+                (spl_token_2022::ID, token_2022_program_account),
+            ],
+            &[
+                Check::success(),
+                Check::account(&vault).lamports(0).build(),
+                Check::account(&escrow).lamports(0).build(),
+            ],
+        );
+        assert_eq!(token_amount(result.get_account(&taker_ata_x).unwrap()), 980_100);
+    }
+
+    #[test]
+    fn test_vesting() {
+        let (mut mollusk, (system_program, system_account), (token_program, token_program_account)) = setup();
+
+        let maker = Pubkey::new_from_array([0x02; 32]);
+        let taker = Pubkey::new_from_array([0x08; 32]);
+        let mint_x = Pubkey::new_from_array([0x03; 32]);
+        let mint_y = Pubkey::new_from_array([0x04; 32]);
+        let maker_ata_x = Pubkey::new_from_array([0x05; 32]);
+        let taker_ata_x = Pubkey::new_from_array([0x09; 32]);
+
+        let seed = 7u64;
+        let (escrow, escrow_bump) = client::escrow_address(&maker, seed);
+        let (vault, vault_bump) = client::vault_address(&escrow, &mint_x);
+
+        mollusk.sysvars.clock.unix_timestamp = 500;
+
+        // A cliff outside of the schedule is rejected.
+        mollusk.process_and_validate_instruction(
+            &client::make_ix(&MakeParams {
+                maker,
+                mint_x,
+                mint_y,
+                maker_ata_x,
+                token_program,
+                seed,
+                amount: 1,
+                deposit: 2_000_000,
+                expiry: 0,
+                taker: Some(taker),
+                flags: 0,
+                collection: None,
+                arbiter: None,
+                hashlock: None,
+                vesting: Some(VestingSchedule { start: 1_000, cliff: 500, end: 3_000 }),
+                auction: None,
+                dutch: None,
+            }),
+            &[
+                (maker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+                (mint_x, mint_account(&mollusk)),
+                (mint_y, mint_account(&mollusk)),
+                (maker_ata_x, token_account(&mollusk, mint_x, maker, 2_000_000)),
+                (vault, AccountSharedData::new(0, 0, &system_program)),
+                (escrow, AccountSharedData::new(0, 0, &system_program)),
+                (system_program, system_account.clone()),
+                (token_program, token_program_account.clone()),
+                config_account(&mollusk, 0, 0, system_program),
+            ],
+            &[Check::err(ProgramError::Custom(EscrowError::InvalidSchedule as u32))],
+        );
+
+        let escrow_state = Escrow {
+            maker: maker.to_bytes(),
+            mint_x: mint_x.to_bytes(),
+            mint_y: mint_y.to_bytes(),
+            taker: taker.to_bytes(),
+            seed,
+            amount: 1,
+            deposit: 2_000_000,
+            bump: escrow_bump,
+            vault_bump,
+            flags: VESTING,
+            ..Default::default()
+        };
+        let schedule = Vesting { start: 1_000, cliff: 1_500, end: 3_000, claimed: 0 };
+
+        let accounts = |vesting: &Vesting| [
+            (maker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (taker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (mint_x, mint_account(&mollusk)),
+            (maker_ata_x, token_account(&mollusk, mint_x, maker, 0)),
+            (taker_ata_x, token_account(&mollusk, mint_x, taker, vesting.claimed)),
+            (vault, token_account(&mollusk, mint_x, escrow, escrow_state.deposit - vesting.claimed)),
+            (escrow, escrow_section_account(&mollusk, &escrow_state, vesting)),
+            (system_program, system_account.clone()),
+            (token_program, token_program_account.clone()),
+        ];
+        let half_claimed_schedule = Vesting { claimed: 1_000_000, ..schedule };
+        let unclaimed = accounts(&schedule);
+        let half_claimed = accounts(&half_claimed_schedule);
+
+        let claim = client::claim_vested_ix(&ClaimVestedParams {
+            maker,
+            taker,
+            seed,
+            mint_x,
+            taker_ata_x,
+            token_program,
+        });
+
+        // Grants can't be refunded, and nothing is claimable before the cliff.
+        mollusk.sysvars.clock.unix_timestamp = 1_400;
+
+        mollusk.process_and_validate_instruction(
+            &refund_instruction(maker, seed, mint_x, maker_ata_x),
+            &unclaimed,
+            &[Check::err(ProgramError::Custom(EscrowError::WrongEscrowType as u32))],
+        );
+        mollusk.process_and_validate_instruction(
+            &claim,
+            &unclaimed,
+            &[Check::err(ProgramError::Custom(EscrowError::NothingToClaim as u32))],
+        );
+
+        // Half way through the schedule half of the deposit is paid out.
+        mollusk.sysvars.clock.unix_timestamp = 2_000;
+
+        let result = mollusk.process_and_validate_instruction(
+            &claim,
+            &unclaimed,
+            &[
+                Check::success(),
+                Check::account(&escrow).data(&escrow_section_data(&escrow_state, &half_claimed_schedule)).build(),
+            ],
+        );
+        assert_eq!(token_amount(result.get_account(&taker_ata_x).unwrap()), 1_000_000);
+        assert_eq!(token_amount(result.get_account(&vault).unwrap()), 1_000_000);
+        assert_eq!(client::decode_vesting(result.get_account(&escrow).unwrap().data()), Some(half_claimed_schedule));
+
+        mollusk.process_and_validate_instruction(
+            &claim,
+            &half_claimed,
+            &[Check::err(ProgramError::Custom(EscrowError::NothingToClaim as u32))],
+        );
+
+        // At the end the rest is paid out and the vault and escrow are closed.
+        mollusk.sysvars.clock.unix_timestamp = 3_000;
+
+        let result = mollusk.process_and_validate_instruction(
+            &claim,
+            &half_claimed,
+            &[
+                Check::success(),
+                Check::account(&vault).lamports(0).build(),
+                Check::account(&escrow).lamports(0).build(),
+            ],
+        );
+        assert_eq!(token_amount(result.get_account(&taker_ata_x).unwrap()), 2_000_000);
+    }
+
+    #[test]
+    fn test_basket_len() {
+        // The account holds the whole struct, trailing padding included.
+        assert_eq!(core::mem::size_of::<Basket>(), Basket::LEN);
+    }
+
+    #[test]
+    fn test_approvals_len() {
+        assert_eq!(core::mem::size_of::<Approvals>(), Approvals::LEN);
+    }
+
+    #[test]
+    fn test_vesting_extreme_schedule() {
+        // A schedule spanning the whole timestamp range doesn't overflow.
+        let grant = Vesting {
+            start: i64::MIN,
+            end: i64::MAX,
+            ..Default::default()
+        };
+
+        assert_eq!(grant.vested(1_000, i64::MIN), 0);
+        assert_eq!(grant.vested(1_000, 0), 500);
+        assert_eq!(grant.vested(1_000, i64::MAX), 1_000);
+    }
+
+    #[test]
+    fn test_vesting_token_2022() {
+        let (mut mollusk, (system_program, system_account), (token_program, token_program_account)) = setup();
+
+        mollusk.add_program(
+            &spl_token_2022::ID,
+            "programs/spl_token_2022-4.0.0",
+            &mollusk_svm::program::loader_keys::LOADER_V3,
+        );
+        let token_2022_program_account = program::create_program_account_loader_v3(&spl_token_2022::ID);
+
+        let maker = Pubkey::new_from_array([0x02; 32]);
+        let taker = Pubkey::new_from_array([0x08; 32]);
+        let mint_x = Pubkey::new_from_array([0x03; 32]);
+        let mint_y = Pubkey::new_from_array([0x04; 32]);
+        let maker_ata_x = Pubkey::new_from_array([0x05; 32]);
+        let taker_ata_x = Pubkey::new_from_array([0x09; 32]);
+
+        let seed = 7u64;
+        let (escrow, _) = client::escrow_address(&maker, seed);
+        let (vault, _) = client::vault_address(&escrow, &mint_x);
+
+        mollusk.sysvars.clock.unix_timestamp = 3_000;
+
+        let make = client::make_ix(&MakeParams {
+            maker,
+            mint_x,
+            mint_y,
+            maker_ata_x,
+            token_program: spl_token_2022::ID,
+            seed,
+            amount: 1,
+            deposit: 1_000_000,
+            expiry: 0,
+            taker: Some(taker),
+            flags: 0,
+            collection: None,
+            arbiter: None,
+            hashlock: None,
+            vesting: Some(VestingSchedule { start: 1_000, cliff: 0, end: 3_000 }),
+            auction: None,
+            dutch: None,
+        });
+
+        let claim = client::claim_vested_ix(&ClaimVestedParams {
+            maker,
+            taker,
+            seed,
+            mint_x,
+            taker_ata_x,
+            token_program: spl_token_2022::ID,
+        });
+
+        // The grant is fully vested, so the claim pays out the 990,000 the vault received and
+        // harvests the fees withheld in the vault to the mint before closing it.
+        let result = mollusk.process_and_validate_instruction_chain(
+            &[make, claim],
+            &[
+                (maker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+                (taker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+                (mint_x, mint_2022_account(&mollusk, 100)),
+                (mint_y, mint_account(&mollusk)),
+                (maker_ata_x, token_2022_account(&mollusk, mint_x, maker, 1_000_000)),
+                (taker_ata_x, token_2022_account(&mollusk, mint_x, taker, 0)),
+                (vault, AccountSharedData::new(0, 0, &system_program)),
+                (escrow, AccountSharedData::new(0, 0, &system_program)),
+                (system_program, system_account),
+                (token_program, token_program_account),
+                config_account(&mollusk, 0, 0, system_program),
+                (spl_token_2022::ID, token_2022_program_account),
+            ],
+            &[
+                Check::success(),
+                Check::account(&vault).lamports(0).build(),
+                Check::account(&escrow).lamports(0).build(),
+            ],
+        );
+        assert_eq!(token_amount(result.get_account(&taker_ata_x).unwrap()), 980_100);
+    }
+
+    #[test]
+    fn test_auction() {
+        let (mut mollusk, (system_program, system_account), (token_program, token_program_account)) = setup();
+
+        let maker = Pubkey::new_from_array([0x02; 32]);
+        let alice = Pubkey::new_from_array([0x08; 32]);
+        let bob = Pubkey::new_from_array([0x0C; 32]);
+        let mint_x = Pubkey::new_from_array([0x03; 32]);
+        let mint_y = Pubkey::new_from_array([0x04; 32]);
+        let maker_ata_x = Pubkey::new_from_array([0x05; 32]);
+        let maker_ata_y = Pubkey::new_from_array([0x0B; 32]);
+        let alice_ata_y = Pubkey::new_from_array([0x09; 32]);
+        let bob_ata_x = Pubkey::new_from_array([0x0D; 32]);
+        let bob_ata_y = Pubkey::new_from_array([0x0E; 32]);
+
+        let seed = 7u64;
+        let (escrow, escrow_bump) = client::escrow_address(&maker, seed);
+        let (vault, vault_bump) = client::vault_address(&escrow, &mint_x);
+        let (bid_vault, bid_vault_bump) = client::bid_vault_address(&escrow, &mint_y);
+
+        mollusk.sysvars.clock.unix_timestamp = 500;
+
+        let make = |min_increment| client::make_ix(&MakeParams {
+            maker,
+            mint_x,
+            mint_y,
+            maker_ata_x,
+            token_program,
+            seed,
+            amount: 1_000,
+            deposit: 2_000_000,
+            expiry: 1_000,
+            taker: None,
+            flags: 0,
+            collection: None,
+            arbiter: None,
+            hashlock: None,
+            vesting: None,
+            auction: Some(AuctionParams { min_increment, token_program_y: token_program }),
+            dutch: None,
+        });
+        let make_accounts = [
+            (maker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (mint_x, mint_account(&mollusk)),
+            (mint_y, mint_account(&mollusk)),
+            (maker_ata_x, token_account(&mollusk, mint_x, maker, 2_000_000)),
+            (vault, AccountSharedData::new(0, 0, &system_program)),
+            (escrow, AccountSharedData::new(0, 0, &system_program)),
+            (system_program, system_account.clone()),
+            (token_program, token_program_account.clone()),
+            config_account(&mollusk, 0, 0, system_program),
+            (bid_vault, AccountSharedData::new(0, 0, &system_program)),
+        ];
+
+        // Bids have to raise the highest one, and opening an auction creates its bid vault.
+        mollusk.process_and_validate_instruction(
+            &make(0),
+            &make_accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::InvalidAuction as u32))],
+        );
+        mollusk.process_and_validate_instruction(
+            &make(100),
+            &make_accounts,
+            &[Check::success(), Check::account(&bid_vault).owner(&token_program).build()],
+        );
+
+        let escrow_state = Escrow {
+            maker: maker.to_bytes(),
+            mint_x: mint_x.to_bytes(),
+            mint_y: mint_y.to_bytes(),
+            seed,
+            amount: 1_000,
+            deposit: 2_000_000,
+            expiry: 1_000,
+            bump: escrow_bump,
+            vault_bump,
+            flags: AUCTION,
+            ..Default::default()
+        };
+        let no_bid_auction = Auction { min_increment: 100, bid_vault_bump, ..Default::default() };
+        let alice_bid = Auction { bidder: alice.to_bytes(), bid: 1_000, ..no_bid_auction };
+        let bob_bid = Auction { bidder: bob.to_bytes(), bid: 1_100, ..no_bid_auction };
+
+        let accounts = |auction: &Auction| [
+            (maker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (alice, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (bob, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (mint_x, mint_account(&mollusk)),
+            (mint_y, mint_account(&mollusk)),
+            (maker_ata_x, token_account(&mollusk, mint_x, maker, 0)),
+            (maker_ata_y, token_account(&mollusk, mint_y, maker, 0)),
+            (alice_ata_y, token_account(&mollusk, mint_y, alice, 1_000)),
+            (bob_ata_x, token_account(&mollusk, mint_x, bob, 0)),
+            (bob_ata_y, token_account(&mollusk, mint_y, bob, 2_000)),
+            (vault, token_account(&mollusk, mint_x, escrow, 2_000_000)),
+            (bid_vault, token_account(&mollusk, mint_y, escrow, auction.bid)),
+            (escrow, escrow_section_account(&mollusk, &escrow_state, auction)),
+            (system_program, system_account.clone()),
+            (token_program, token_program_account.clone()),
+            config_account(&mollusk, 0, 0, system_program),
+        ];
+        let no_bid = accounts(&no_bid_auction);
+        let alice_leads = accounts(&alice_bid);
+        let bob_leads = accounts(&bob_bid);
+
+        let bid = |bidder, bidder_ata_y, previous_bidder, previous_bidder_ata_y, amount| client::bid_ix(&BidParams {
+            bidder,
+            maker,
+            seed,
+            mint_y,
+            bidder_ata_y,
+            previous_bidder,
+            previous_bidder_ata_y,
+            token_program_y: token_program,
+            amount,
+        });
+
+        // The first bid has to meet the reserve price.
+        mollusk.process_and_validate_instruction(
+            &bid(alice, alice_ata_y, system_program, system_program, 900),
+            &no_bid,
+            &[Check::err(ProgramError::Custom(EscrowError::BidTooLow as u32))],
+        );
+        let result = mollusk.process_and_validate_instruction(
+            &bid(alice, alice_ata_y, system_program, system_program, 1_000),
+            &no_bid,
+            &[Check::success(), Check::account(&escrow).data(&escrow_section_data(&escrow_state, &alice_bid)).build()],
+        );
+        assert_eq!(token_amount(result.get_account(&bid_vault).unwrap()), 1_000);
+
+        // A higher bid has to add the minimum increment and refunds the previous bidder.
+        mollusk.process_and_validate_instruction(
+            &bid(bob, bob_ata_y, alice, alice_ata_y, 1_050),
+            &alice_leads,
+            &[Check::err(ProgramError::Custom(EscrowError::BidTooLow as u32))],
+        );
+        let result = mollusk.process_and_validate_instruction(
+            &bid(bob, bob_ata_y, alice, alice_ata_y, 1_100),
+            &alice_leads,
+            &[Check::success(), Check::account(&escrow).data(&escrow_section_data(&escrow_state, &bob_bid)).build()],
+        );
+        assert_eq!(token_amount(result.get_account(&bid_vault).unwrap()), 1_100);
+        assert_eq!(token_amount(result.get_account(&alice_ata_y).unwrap()), 2_000);
+
+        // Once bid on, the auction can't be refunded or settled before its end.
+        let settle = client::settle_ix(&SettleParams {
+            maker,
+            winner: bob,
+            seed,
+            mint_x,
+            mint_y,
+            winner_ata_x: bob_ata_x,
+            maker_ata_y,
+            token_program_x: token_program,
+            token_program_y: token_program,
+            treasury: system_program,
+        });
+
+        mollusk.process_and_validate_instruction(
+            &refund_instruction(maker, seed, mint_x, maker_ata_x),
+            &bob_leads,
+            &[Check::err(ProgramError::Custom(EscrowError::AuctionHasBids as u32))],
+        );
+        mollusk.process_and_validate_instruction(
+            &settle,
+            &bob_leads,
+            &[Check::err(ProgramError::Custom(EscrowError::AuctionActive as u32))],
+        );
+
+        // After the end bids are refused, and settling pays both sides and closes everything.
+        mollusk.sysvars.clock.unix_timestamp = 1_000;
+
+        mollusk.process_and_validate_instruction(
+            &bid(alice, alice_ata_y, bob, bob_ata_y, 1_200),
+            &bob_leads,
+            &[Check::err(ProgramError::Custom(EscrowError::EscrowExpired as u32))],
+        );
+
+        let result = mollusk.process_and_validate_instruction(
+            &settle,
+            &bob_leads,
+            &[
+                Check::success(),
+                Check::account(&vault).lamports(0).build(),
+                Check::account(&bid_vault).lamports(0).build(),
+                Check::account(&escrow).lamports(0).build(),
+            ],
+        );
+        assert_eq!(token_amount(result.get_account(&bob_ata_x).unwrap()), 2_000_000);
+        assert_eq!(token_amount(result.get_account(&maker_ata_y).unwrap()), 1_100);
+    }
+
+    #[test]
+    fn test_dutch_auction_extreme_schedule() {
+        // A decay spanning the whole timestamp range doesn't overflow.
+        let decay = Dutch {
+            floor: 1_000,
+            start: i64::MIN,
+            end: i64::MAX,
+            step: 0,
+        };
+
+        assert_eq!(decay.price(2_000, i64::MIN), 2_000);
+        assert_eq!(decay.price(2_000, 0), 1_500);
+        assert_eq!(decay.price(2_000, i64::MAX), 1_000);
+
+        // So does the longest step, one of which has elapsed by then.
+        let stepwise = Dutch { step: i64::MAX, ..decay };
+        assert_eq!(stepwise.price(2_000, -2), 2_000);
+        assert_eq!(stepwise.price(2_000, 0), 1_501);
+    }
+
+    #[test]
+    fn test_dutch_auction() {
+        let (mut mollusk, (system_program, system_account), (token_program, token_program_account)) = setup();
+
+        let maker = Pubkey::new_from_array([0x02; 32]);
+        let taker = Pubkey::new_from_array([0x08; 32]);
+        let mint_x = Pubkey::new_from_array([0x03; 32]);
+        let mint_y = Pubkey::new_from_array([0x04; 32]);
+        let maker_ata_y = Pubkey::new_from_array([0x0B; 32]);
+        let taker_ata_x = Pubkey::new_from_array([0x09; 32]);
+        let taker_ata_y = Pubkey::new_from_array([0x0A; 32]);
+
+        let seed = 7u64;
+        let (escrow, escrow_bump) = client::escrow_address(&maker, seed);
+        let (vault, vault_bump) = client::vault_address(&escrow, &mint_x);
+
+        // 2,000,000 of mint_x offered for 1,000,000 of mint_y, dropping to 400,000 between 1,000 and 2,000.
+        let escrow_state = Escrow {
+            maker: maker.to_bytes(),
+            mint_x: mint_x.to_bytes(),
+            mint_y: mint_y.to_bytes(),
+            seed,
+            amount: 1_000_000,
+            deposit: 2_000_000,
+            bump: escrow_bump,
+            vault_bump,
+            flags: DUTCH,
+            ..Default::default()
+        };
+        let linear = Dutch { floor: 400_000, start: 1_000, end: 2_000, step: 0 };
+        let stepwise = Dutch { step: 300, ..linear };
+
+        let accounts = |decay: &Dutch| [
+            (taker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (maker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (mint_x, mint_account(&mollusk)),
+            (mint_y, mint_account(&mollusk)),
+            (maker_ata_y, token_account(&mollusk, mint_y, maker, 0)),
+            (taker_ata_x, token_account(&mollusk, mint_x, taker, 0)),
+            (taker_ata_y, token_account(&mollusk, mint_y, taker, 1_000_000)),
+            (vault, token_account(&mollusk, mint_x, escrow, 2_000_000)),
+            (escrow, escrow_section_account(&mollusk, &escrow_state, decay)),
+            (system_program, system_account.clone()),
+            (token_program, token_program_account.clone()),
+            config_account(&mollusk, 0, 0, system_program),
+        ];
+        let linear_accounts = accounts(&linear);
+        let stepwise_accounts = accounts(&stepwise);
+
+        let take = |fill| take_instruction(taker, maker, seed, mint_x, mint_y, taker_ata_x, taker_ata_y, maker_ata_y, fill);
+
+        // Half way through a linear decay the price is half way down to the floor.
+        mollusk.sysvars.clock.unix_timestamp = 1_500;
+
+        mollusk.process_and_validate_instruction(
+            &take(700_001),
+            &linear_accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::InvalidFillAmount as u32))],
+        );
+
+        // A partial fill scales the rest of the curve with the remaining deposit.
+        let result = mollusk.process_and_validate_instruction(
+            &take(350_000),
+            &linear_accounts,
+            &[
+                Check::success(),
+                Check::account(&escrow).data(&escrow_section_data(
+                    &Escrow { amount: 500_000, deposit: 1_000_000, ..escrow_state },
+                    &Dutch { floor: 200_000, ..linear },
+                )).build(),
+            ],
+        );
+        assert_eq!(token_amount(result.get_account(&taker_ata_x).unwrap()), 1_000_000);
+
+        // A stepwise decay only dropped once, 300 seconds in.
+        mollusk.process_and_validate_instruction(
+            &take(820_001),
+            &stepwise_accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::InvalidFillAmount as u32))],
+        );
+        let result = mollusk.process_and_validate_instruction(
+            &take(820_000),
+            &stepwise_accounts,
+            &[Check::success(), Check::account(&escrow).lamports(0).build()],
+        );
+        assert_eq!(token_amount(result.get_account(&taker_ata_x).unwrap()), 2_000_000);
+        assert_eq!(token_amount(result.get_account(&maker_ata_y).unwrap()), 820_000);
+
+        // Past the end the price stays at the floor.
+        mollusk.sysvars.clock.unix_timestamp = 5_000;
+
+        let result = mollusk.process_and_validate_instruction(
+            &take(400_000),
+            &linear_accounts,
+            &[Check::success(), Check::account(&vault).lamports(0).build()],
+        );
+        assert_eq!(token_amount(result.get_account(&taker_ata_x).unwrap()), 2_000_000);
+    }
+
+    #[test]
+    fn test_match() {
+        let (mollusk, (system_program, system_account), (token_program, token_program_account)) = setup();
+
+        let matcher = Pubkey::new_from_array([0x0F; 32]);
+        let maker_a = Pubkey::new_from_array([0x02; 32]);
+        let maker_b = Pubkey::new_from_array([0x08; 32]);
+        let mint_x = Pubkey::new_from_array([0x03; 32]);
+        let mint_y = Pubkey::new_from_array([0x04; 32]);
+        let maker_a_ata_y = Pubkey::new_from_array([0x0B; 32]);
+        let maker_b_ata_x = Pubkey::new_from_array([0x09; 32]);
+        let matcher_ata_x = Pubkey::new_from_array([0x0D; 32]);
+        let matcher_ata_y = Pubkey::new_from_array([0x0E; 32]);
+
+        let (escrow_a, escrow_a_bump) = client::escrow_address(&maker_a, 1);
+        let (escrow_b, escrow_b_bump) = client::escrow_address(&maker_b, 2);
+        let (vault_a, vault_a_bump) = client::vault_address(&escrow_a, &mint_x);
+        let (vault_b, vault_b_bump) = client::vault_address(&escrow_b, &mint_y);
+
+        // A offers 2,000,000 of mint_x for 1,000,000 of mint_y.
+        let escrow_a_state = Escrow {
+            maker: maker_a.to_bytes(),
+            mint_x: mint_x.to_bytes(),
+            mint_y: mint_y.to_bytes(),
+            seed: 1,
+            amount: 1_000_000,
+            deposit: 2_000_000,
+            bump: escrow_a_bump,
+            vault_bump: vault_a_bump,
+            ..Default::default()
+        };
+
+        let accounts = |escrow_b_deposit: u64| [
+            (matcher, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (maker_a, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (maker_b, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (mint_x, mint_account(&mollusk)),
+            (mint_y, mint_account(&mollusk)),
+            (escrow_a, escrow_account(&mollusk, &escrow_a_state)),
+            (escrow_b, escrow_account(&mollusk, &Escrow {
+                maker: maker_b.to_bytes(),
+                mint_x: mint_y.to_bytes(),
+                mint_y: mint_x.to_bytes(),
+                seed: 2,
+                amount: 2_000_000,
+                deposit: escrow_b_deposit,
+                bump: escrow_b_bump,
+                vault_bump: vault_b_bump,
+                ..Default::default()
+            })),
+            (vault_a, token_account(&mollusk, mint_x, escrow_a, 2_000_000)),
+            (vault_b, token_account(&mollusk, mint_y, escrow_b, escrow_b_deposit)),
+            (maker_a_ata_y, token_account(&mollusk, mint_y, maker_a, 0)),
+            (maker_b_ata_x, token_account(&mollusk, mint_x, maker_b, 0)),
+            (matcher_ata_x, token_account(&mollusk, mint_x, matcher, 0)),
+            (matcher_ata_y, token_account(&mollusk, mint_y, matcher, 0)),
+            (system_program, system_account.clone()),
+            (token_program, token_program_account.clone()),
+            config_account(&mollusk, 0, 0, system_program),
+        ];
+
+        let instruction = client::match_ix(&MatchParams {
+            matcher,
+            maker_a,
+            seed_a: 1,
+            maker_b,
+            seed_b: 2,
+            mint_x,
+            mint_y,
+            maker_a_ata_y,
+            maker_b_ata_x,
+            matcher_ata_x,
+            matcher_ata_y,
+            token_program_x: token_program,
+            token_program_y: token_program,
+            treasury_x: system_program,
+            treasury_y: system_program,
+            fill_a: 1_000_000,
+            fill_b: 2_000_000,
+        });
+
+        // B offering 900,000 of mint_y for 2,000,000 of mint_x doesn't cover the price of A.
+        mollusk.process_and_validate_instruction(
+            &instruction,
+            &accounts(900_000),
+            &[Check::err(ProgramError::Custom(EscrowError::PriceMismatch as u32))],
+        );
+
+        // B offering 1,200,000 crosses A, leaving 200,000 of mint_y to the matcher.
+        let result = mollusk.process_and_validate_instruction(
+            &instruction,
+            &accounts(1_200_000),
+            &[
+                Check::success(),
+                Check::account(&vault_a).lamports(0).build(),
+                Check::account(&vault_b).lamports(0).build(),
+                Check::account(&escrow_a).lamports(0).build(),
+                Check::account(&escrow_b).lamports(0).build(),
+            ],
+        );
+        assert_eq!(token_amount(result.get_account(&maker_a_ata_y).unwrap()), 1_000_000);
+        assert_eq!(token_amount(result.get_account(&maker_b_ata_x).unwrap()), 2_000_000);
+        assert_eq!(token_amount(result.get_account(&matcher_ata_x).unwrap()), 0);
+        assert_eq!(token_amount(result.get_account(&matcher_ata_y).unwrap()), 200_000);
+    }
+
+    #[test]
+    fn test_expire() {
+        let (mut mollusk, (system_program, system_account), (token_program, token_program_account)) = setup();
+
+        let maker = Pubkey::new_from_array([0x02; 32]);
+        let keeper = Pubkey::new_from_array([0x0F; 32]);
+        let mint_x = Pubkey::new_from_array([0x03; 32]);
+        let mint_y = Pubkey::new_from_array([0x04; 32]);
+        let maker_ata_x = Pubkey::new_from_array([0x05; 32]);
+        let keeper_ata_x = Pubkey::new_from_array([0x09; 32]);
+
+        let seed = 7u64;
+        let (escrow, escrow_bump) = client::escrow_address(&maker, seed);
+        let (vault, vault_bump) = client::vault_address(&escrow, &mint_x);
+
+        let escrow_state = Escrow {
+            maker: maker.to_bytes(),
+            mint_x: mint_x.to_bytes(),
+            mint_y: mint_y.to_bytes(),
+            seed,
+            amount: 1_000_000,
+            deposit: 2_000_000,
+            expiry: 1_000,
+            bump: escrow_bump,
+            vault_bump,
+            ..Default::default()
+        };
+
+        let accounts = [
+            (keeper, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (maker, AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program)),
+            (mint_x, mint_account(&mollusk)),
+            (maker_ata_x, token_account(&mollusk, mint_x, maker, 0)),
+            (keeper_ata_x, token_account(&mollusk, mint_x, keeper, 0)),
+            (vault, token_account(&mollusk, mint_x, escrow, 2_000_000)),
+            (escrow, escrow_account(&mollusk, &escrow_state)),
+            (system_program, system_account),
+            (token_program, token_program_account),
+        ];
+        let freed = accounts[5].1.lamports() + accounts[6].1.lamports();
+
+        let expire = |maker_ata_x| client::expire_ix(&keeper, &RefundParams {
+            maker,
+            seed,
+            mint_x,
+            maker_ata_x,
+            token_program,
+            multisig: false,
+            auction: None,
+        });
+
+        // Nobody but the maker can close the escrow before its expiry.
+        mollusk.sysvars.clock.unix_timestamp = 999;
+        mollusk.process_and_validate_instruction(
+            &expire(maker_ata_x),
+            &accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::NotExpired as u32))],
+        );
+
+        // Once expired, anyone can send the deposit back to the maker for a tip.
+        mollusk.sysvars.clock.unix_timestamp = 1_000;
+        mollusk.process_and_validate_instruction(
+            &expire(keeper_ata_x),
+            &accounts,
+            &[Check::err(ProgramError::Custom(EscrowError::InvalidTokenAccount as u32))],
+        );
+
+        // The keeper has to sign for the tip it collects.
+        let mut unsigned = expire(maker_ata_x);
+        unsigned.accounts[0].is_signer = false;
+        mollusk.process_and_validate_instruction(
+            &unsigned,
+            &accounts,
+            &[Check::err(ProgramError::MissingRequiredSignature)],
+        );
+
+        let result = mollusk.process_and_validate_instruction(
+            &expire(maker_ata_x),
+            &accounts,
+            &[
+                Check::success(),
+                Check::account(&vault).lamports(0).build(),
+                Check::account(&escrow).lamports(0).build(),
+                Check::account(&keeper).lamports(LAMPORTS_PER_SOL + client::EXPIRE_TIP).build(),
+                Check::account(&maker).lamports(LAMPORTS_PER_SOL + freed - client::EXPIRE_TIP).build(),
+            ],
+        );
+        assert_eq!(token_amount(result.get_account(&maker_ata_x).unwrap()), 2_000_000);
+    }
+}
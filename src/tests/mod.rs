@@ -1,3 +1 @@
 pub mod escrow_tests;
-
-pub use escrow_tests::*;
\ No newline at end of file
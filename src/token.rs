@@ -0,0 +1,291 @@
+use pinocchio::{
+    account_info::AccountInfo,
+    instruction::{AccountMeta, Instruction, Signer},
+    program::{get_return_data, invoke_signed},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvars::{rent::Rent, Sysvar},
+    ProgramResult,
+};
+use pinocchio_system::instructions::CreateAccount;
+use pinocchio_token::state::{Mint, TokenAccount};
+
+pub const TOKEN_2022_PROGRAM_ID: Pubkey = five8_const::decode_32_const("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+
+/// Whether `program_id` is the SPL Token or the Token-2022 program.
+pub fn is_token_program(program_id: &Pubkey) -> bool {
+    program_id == &pinocchio_token::ID || program_id == &TOKEN_2022_PROGRAM_ID
+}
+
+/// Reads the base token account state of an account owned by either token program.
+///
+/// Token-2022 accounts keep the SPL Token layout and append their extensions after it,
+/// so only the first `TokenAccount::LEN` bytes are read.
+pub fn token_account(account_info: &AccountInfo) -> Result<&TokenAccount, ProgramError> {
+    if !is_token_program(account_info.owner()) {
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    if account_info.data_len() < TokenAccount::LEN {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(unsafe { TokenAccount::from_bytes(account_info.borrow_data_unchecked()) })
+}
+
+/// Reads the base mint state of an account owned by either token program.
+pub fn mint(account_info: &AccountInfo) -> Result<&Mint, ProgramError> {
+    if !is_token_program(account_info.owner()) {
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    if account_info.data_len() < Mint::LEN {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(unsafe { Mint::from_bytes(account_info.borrow_data_unchecked()) })
+}
+
+/// Whether `mint` is an NFT mint, with 0 decimals and a supply of 1.
+pub fn is_nft(mint_info: &AccountInfo) -> Result<bool, ProgramError> {
+    let mint = mint(mint_info)?;
+    Ok(mint.decimals() == 0 && mint.supply() == 1)
+}
+
+/// Creates `account` as a token account of `mint` owned by `owner`, sized for the
+/// extensions required by Token-2022 mints. `signers` must sign for `account`.
+pub fn create_token_account(
+    payer: &AccountInfo,
+    account: &AccountInfo,
+    mint: &AccountInfo,
+    owner: &Pubkey,
+    signers: &[Signer],
+) -> ProgramResult {
+    let token_program = mint.owner();
+
+    let space = if token_program == &TOKEN_2022_PROGRAM_ID {
+        // `GetAccountDataSize` returns the size as a u64 return data
+        invoke_signed(
+            &Instruction {
+                program_id: token_program,
+                accounts: &[AccountMeta::readonly(mint.key())],
+                data: &[21],
+            },
+            &[mint],
+            &[],
+        )?;
+
+        let return_data = get_return_data().ok_or(ProgramError::InvalidAccountData)?;
+        let size: [u8; 8] = return_data.as_slice().try_into().map_err(|_| ProgramError::InvalidAccountData)?;
+        u64::from_le_bytes(size)
+    } else {
+        TokenAccount::LEN as u64
+    };
+
+    CreateAccount{
+        from: payer,
+        to: account,
+        lamports: Rent::get()?.minimum_balance(space as usize),
+        space,
+        owner: token_program,
+    }.invoke_signed(signers)?;
+
+    InitializeAccount3{
+        account,
+        mint,
+        owner,
+        token_program,
+    }.invoke()
+}
+
+/// `InitializeAccount3` sent to the token program given in `token_program`.
+///
+/// ### Accounts:
+///   0. `[WRITE]` The account to initialize.
+///   1. `[]` The mint this account will be associated with.
+pub struct InitializeAccount3<'a> {
+    pub account: &'a AccountInfo,
+    pub mint: &'a AccountInfo,
+    pub owner: &'a Pubkey,
+    pub token_program: &'a Pubkey,
+}
+
+impl InitializeAccount3<'_> {
+    pub fn invoke(&self) -> ProgramResult {
+        let account_metas: [AccountMeta; 2] = [
+            AccountMeta::writable(self.account.key()),
+            AccountMeta::readonly(self.mint.key()),
+        ];
+
+        // Instruction data layout:
+        // -  [0]: instruction discriminator
+        // -  [1..33]: owner
+        let mut instruction_data = [0; 33];
+        instruction_data[0] = 18;
+        instruction_data[1..].copy_from_slice(self.owner);
+
+        let instruction = Instruction {
+            program_id: self.token_program,
+            accounts: &account_metas,
+            data: &instruction_data,
+        };
+
+        invoke_signed(&instruction, &[self.account, self.mint], &[])
+    }
+}
+
+/// `TransferChecked` sent to the token program given in `token_program`.
+///
+/// ### Accounts:
+///   0. `[WRITE]` The source account.
+///   1. `[]` The token mint.
+///   2. `[WRITE]` The destination account.
+///   3. `[SIGNER]` The source account's owner/delegate.
+pub struct TransferChecked<'a> {
+    pub from: &'a AccountInfo,
+    pub mint: &'a AccountInfo,
+    pub to: &'a AccountInfo,
+    pub authority: &'a AccountInfo,
+    pub amount: u64,
+    pub decimals: u8,
+    pub token_program: &'a Pubkey,
+}
+
+impl TransferChecked<'_> {
+    #[inline(always)]
+    pub fn invoke(&self) -> ProgramResult {
+        self.invoke_signed(&[])
+    }
+
+    pub fn invoke_signed(&self, signers: &[Signer]) -> ProgramResult {
+        let account_metas: [AccountMeta; 4] = [
+            AccountMeta::writable(self.from.key()),
+            AccountMeta::readonly(self.mint.key()),
+            AccountMeta::writable(self.to.key()),
+            AccountMeta::readonly_signer(self.authority.key()),
+        ];
+
+        // Instruction data layout:
+        // -  [0]: instruction discriminator
+        // -  [1..9]: amount
+        // -  [9]: decimals
+        let mut instruction_data = [0; 10];
+        instruction_data[0] = 12;
+        instruction_data[1..9].copy_from_slice(&self.amount.to_le_bytes());
+        instruction_data[9] = self.decimals;
+
+        let instruction = Instruction {
+            program_id: self.token_program,
+            accounts: &account_metas,
+            data: &instruction_data,
+        };
+
+        invoke_signed(&instruction, &[self.from, self.mint, self.to, self.authority], signers)
+    }
+}
+
+/// `CloseAccount` sent to the token program given in `token_program`.
+///
+/// ### Accounts:
+///   0. `[WRITE]` The account to close.
+///   1. `[WRITE]` The destination account.
+///   2. `[SIGNER]` The account's owner.
+pub struct CloseAccount<'a> {
+    pub account: &'a AccountInfo,
+    pub destination: &'a AccountInfo,
+    pub authority: &'a AccountInfo,
+    pub token_program: &'a Pubkey,
+}
+
+impl CloseAccount<'_> {
+    pub fn invoke_signed(&self, signers: &[Signer]) -> ProgramResult {
+        let account_metas: [AccountMeta; 3] = [
+            AccountMeta::writable(self.account.key()),
+            AccountMeta::writable(self.destination.key()),
+            AccountMeta::readonly_signer(self.authority.key()),
+        ];
+
+        let instruction = Instruction {
+            program_id: self.token_program,
+            accounts: &account_metas,
+            data: &[9],
+        };
+
+        invoke_signed(&instruction, &[self.account, self.destination, self.authority], signers)
+    }
+}
+
+/// Closes an emptied `vault` to `destination`, first harvesting the transfer fees
+/// withheld in Token-2022 vaults, which can't be closed while they hold any.
+/// `signers` must sign for `authority`, the owner of the vault.
+pub fn close_vault(
+    vault: &AccountInfo,
+    mint: &AccountInfo,
+    destination: &AccountInfo,
+    authority: &AccountInfo,
+    token_program: &Pubkey,
+    signers: &[Signer],
+) -> ProgramResult {
+    if withheld_amount(vault) > 0 {
+        HarvestWithheldTokensToMint{
+            mint,
+            account: vault,
+        }.invoke()?;
+    }
+
+    CloseAccount{
+        account: vault,
+        destination,
+        authority,
+        token_program,
+    }.invoke_signed(signers)
+}
+
+/// Transfer fees withheld in a Token-2022 account by the transfer fee extension,
+/// `0` for accounts without the extension.
+pub fn withheld_amount(account_info: &AccountInfo) -> u64 {
+    // Extension type of `TransferFeeAmount` in the Token-2022 TLV data
+    const TRANSFER_FEE_AMOUNT: u16 = 2;
+
+    let data = unsafe { account_info.borrow_data_unchecked() };
+
+    // Extensions start after the base account and its account type byte
+    let mut offset = TokenAccount::LEN + 1;
+    while offset + 4 <= data.len() {
+        let extension = u16::from_le_bytes([data[offset], data[offset + 1]]);
+        let length = u16::from_le_bytes([data[offset + 2], data[offset + 3]]) as usize;
+        offset += 4;
+
+        if extension == TRANSFER_FEE_AMOUNT && offset + 8 <= data.len() {
+            return u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+        }
+        offset += length;
+    }
+
+    0
+}
+
+/// Token-2022 `HarvestWithheldTokensToMint`, moving the transfer fees withheld in
+/// `account` to the mint so the account can be closed. Anyone can harvest.
+///
+/// ### Accounts:
+///   0. `[WRITE]` The token mint.
+///   1. `[WRITE]` The account to harvest from.
+pub struct HarvestWithheldTokensToMint<'a> {
+    pub mint: &'a AccountInfo,
+    pub account: &'a AccountInfo,
+}
+
+impl HarvestWithheldTokensToMint<'_> {
+    pub fn invoke(&self) -> ProgramResult {
+        let account_metas: [AccountMeta; 2] = [
+            AccountMeta::writable(self.mint.key()),
+            AccountMeta::writable(self.account.key()),
+        ];
+
+        // Transfer fee extension instruction, `HarvestWithheldTokensToMint` sub-instruction
+        let instruction = Instruction {
+            program_id: &TOKEN_2022_PROGRAM_ID,
+            accounts: &account_metas,
+            data: &[26, 4],
+        };
+
+        invoke_signed(&instruction, &[self.mint, self.account], &[])
+    }
+}